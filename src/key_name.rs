@@ -0,0 +1,141 @@
+use rdev::{Button, EventType, Key};
+
+macro_rules! decl_key_names {
+    ($($key:ident),* $(,)?) => {
+        const KEYS: &[(&str, Key)] = &[
+            $(
+                (stringify!($key), Key::$key),
+            )*
+        ];
+    };
+}
+
+#[rustfmt::skip]
+decl_key_names!(
+    Alt, AltGr, Backspace, CapsLock, ControlLeft, ControlRight, Delete, DownArrow, End, Escape,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Home, LeftArrow, MetaLeft, MetaRight, PageDown, PageUp, Return, RightArrow, ShiftLeft,
+    ShiftRight, Space, Tab, UpArrow, PrintScreen, ScrollLock, Pause, NumLock, BackQuote,
+    Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9, Num0, Minus, Equal,
+    KeyQ, KeyW, KeyE, KeyR, KeyT, KeyY, KeyU, KeyI, KeyO, KeyP, LeftBracket, RightBracket,
+    KeyA, KeyS, KeyD, KeyF, KeyG, KeyH, KeyJ, KeyK, KeyL, SemiColon, Quote, BackSlash,
+    IntlBackslash, KeyZ, KeyX, KeyC, KeyV, KeyB, KeyN, KeyM, Comma, Dot, Slash, Insert,
+    KpReturn, KpMinus, KpPlus, KpMultiply, KpDivide,
+    Kp0, Kp1, Kp2, Kp3, Kp4, Kp5, Kp6, Kp7, Kp8, Kp9, KpDelete, Function,
+);
+
+/// Stable name of a key, independent of how rdev lays out its enum
+pub fn key_to_name(key: Key) -> String {
+    match key {
+        Key::Unknown(code) => format!("Unknown({code})"),
+        key => KEYS
+            .iter()
+            .find(|(_, known_key)| *known_key == key)
+            .map(|(name, _)| (*name).to_owned())
+            .unwrap_or_else(|| format!("{key:?}")),
+    }
+}
+
+pub fn key_from_name(name: &str) -> Option<Key> {
+    if let Some(code) = parse_unknown(name) {
+        return Some(Key::Unknown(code));
+    }
+    KEYS.iter()
+        .find(|(known_name, _)| *known_name == name)
+        .map(|(_, key)| *key)
+}
+
+pub fn button_to_name(button: Button) -> String {
+    match button {
+        Button::Left => "Left".into(),
+        Button::Right => "Right".into(),
+        Button::Middle => "Middle".into(),
+        Button::Unknown(code) => format!("Unknown({code})"),
+    }
+}
+
+pub fn button_from_name(name: &str) -> Option<Button> {
+    match name {
+        "Left" => Some(Button::Left),
+        "Right" => Some(Button::Right),
+        "Middle" => Some(Button::Middle),
+        name => parse_unknown(name)
+            .and_then(|code| u8::try_from(code).ok())
+            .map(Button::Unknown),
+    }
+}
+
+fn parse_unknown(name: &str) -> Option<u32> {
+    name.strip_prefix("Unknown(")?
+        .strip_suffix(')')?
+        .parse()
+        .ok()
+}
+
+/// Key producing `c` on a US QWERTY layout, and whether shift must be held
+pub fn keystroke_for_char(c: char) -> Option<(Key, bool)> {
+    let unshifted = |key| Some((key, false));
+    let shifted = |key| Some((key, true));
+    match c {
+        'a'..='z' | 'A'..='Z' => {
+            let name = format!("Key{}", c.to_ascii_uppercase());
+            let key = key_from_name(&name)?;
+            Some((key, c.is_ascii_uppercase()))
+        }
+        '0'..='9' => key_from_name(&format!("Num{c}")).map(|key| (key, false)),
+        ' ' => unshifted(Key::Space),
+        '\n' => unshifted(Key::Return),
+        '\t' => unshifted(Key::Tab),
+        '`' => unshifted(Key::BackQuote),
+        '-' => unshifted(Key::Minus),
+        '=' => unshifted(Key::Equal),
+        '[' => unshifted(Key::LeftBracket),
+        ']' => unshifted(Key::RightBracket),
+        '\\' => unshifted(Key::BackSlash),
+        ';' => unshifted(Key::SemiColon),
+        '\'' => unshifted(Key::Quote),
+        ',' => unshifted(Key::Comma),
+        '.' => unshifted(Key::Dot),
+        '/' => unshifted(Key::Slash),
+        '~' => shifted(Key::BackQuote),
+        '!' => shifted(Key::Num1),
+        '@' => shifted(Key::Num2),
+        '#' => shifted(Key::Num3),
+        '$' => shifted(Key::Num4),
+        '%' => shifted(Key::Num5),
+        '^' => shifted(Key::Num6),
+        '&' => shifted(Key::Num7),
+        '*' => shifted(Key::Num8),
+        '(' => shifted(Key::Num9),
+        ')' => shifted(Key::Num0),
+        '_' => shifted(Key::Minus),
+        '+' => shifted(Key::Equal),
+        '{' => shifted(Key::LeftBracket),
+        '}' => shifted(Key::RightBracket),
+        '|' => shifted(Key::BackSlash),
+        ':' => shifted(Key::SemiColon),
+        '"' => shifted(Key::Quote),
+        '<' => shifted(Key::Comma),
+        '>' => shifted(Key::Dot),
+        '?' => shifted(Key::Slash),
+        _ => None,
+    }
+}
+
+/// Character typed by `key` on a US QWERTY layout, the reverse of [`keystroke_for_char`]
+pub fn char_for_keystroke(key: Key, shift: bool) -> Option<char> {
+    (' '..='~')
+        .chain(['\n', '\t'])
+        .find(|c| keystroke_for_char(*c) == Some((key, shift)))
+}
+
+/// Key events typing `c` on a US QWERTY layout
+pub fn key_events_for_char(c: char) -> Option<Vec<EventType>> {
+    let (key, shift) = keystroke_for_char(c)?;
+    let mut events = vec![EventType::KeyPress(key), EventType::KeyRelease(key)];
+    if shift {
+        events.insert(0, EventType::KeyPress(Key::ShiftLeft));
+        events.push(EventType::KeyRelease(Key::ShiftLeft));
+    }
+    Some(events)
+}
@@ -0,0 +1,115 @@
+//! Upgrades older macro files to the current format.
+//!
+//! Each past format keeps its own frozen types here so that changes to the in-memory
+//! events never break files written by older versions. When the format changes, move the
+//! current format module to a frozen one and add its upgrade step to [`upgrade`].
+
+use std::time::{Duration, SystemTime};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    key_name::{button_to_name, key_to_name},
+    macro_file::{Error, v1},
+};
+
+/// Version 0 is the bare list of events as serialized by the first powerkey releases
+mod v0 {
+    use super::*;
+
+    #[derive(Deserialize)]
+    pub struct Event {
+        pub time: SystemTime,
+        pub kind: EventKind,
+    }
+
+    #[derive(Deserialize)]
+    pub enum EventKind {
+        Input(rdev::EventType),
+        FocusChange { window_title: String },
+        Delay(Duration),
+        YieldFocus,
+    }
+}
+
+fn version_of(value: &Value) -> Result<u64, Error> {
+    match value {
+        Value::Array(_) => Ok(0),
+        Value::Object(object) => object
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or(Error::MissingVersion),
+        _ => Err(Error::MissingVersion),
+    }
+}
+
+pub fn upgrade(value: Value, name: &str) -> Result<v1::Document, Error> {
+    match version_of(&value)? {
+        0 => Ok(v0_to_v1(
+            serde_json::from_value(value).map_err(Error::Format)?,
+            name,
+        )),
+        v1::VERSION => serde_json::from_value(value).map_err(Error::Format),
+        version => Err(Error::UnsupportedVersion(version)),
+    }
+}
+
+fn v0_to_v1(events: Vec<v0::Event>, name: &str) -> v1::Document {
+    let created = events
+        .iter()
+        .map(|event| v1::to_millis(event.time))
+        .min()
+        .unwrap_or_else(|| v1::to_millis(SystemTime::now()));
+    let modified = events
+        .iter()
+        .map(|event| v1::to_millis(event.time))
+        .max()
+        .unwrap_or(created);
+    let events = events
+        .into_iter()
+        .map(|event| v1::Event {
+            time: v1::to_millis(event.time),
+            kind: match event.kind {
+                v0::EventKind::Input(event_type) => match event_type {
+                    rdev::EventType::KeyPress(key) => v1::EventKind::KeyPress {
+                        key: key_to_name(key),
+                    },
+                    rdev::EventType::KeyRelease(key) => v1::EventKind::KeyRelease {
+                        key: key_to_name(key),
+                    },
+                    rdev::EventType::ButtonPress(button) => v1::EventKind::ButtonPress {
+                        button: button_to_name(button),
+                    },
+                    rdev::EventType::ButtonRelease(button) => v1::EventKind::ButtonRelease {
+                        button: button_to_name(button),
+                    },
+                    rdev::EventType::MouseMove { x, y } => v1::EventKind::MouseMove {
+                        x,
+                        y,
+                        window_x: None,
+                        window_y: None,
+                    },
+                    rdev::EventType::Wheel { delta_x, delta_y } => {
+                        v1::EventKind::Wheel { delta_x, delta_y }
+                    }
+                },
+                v0::EventKind::FocusChange { window_title } => {
+                    v1::EventKind::FocusChange { window_title }
+                }
+                v0::EventKind::Delay(duration) => v1::EventKind::Delay {
+                    milliseconds: duration.as_millis() as u64,
+                },
+                v0::EventKind::YieldFocus => v1::EventKind::YieldFocus,
+            },
+        })
+        .collect();
+    v1::Document {
+        version: v1::VERSION,
+        name: name.to_owned(),
+        created,
+        modified,
+        metadata: Default::default(),
+        events,
+    }
+}
@@ -0,0 +1,310 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::subscription::global_event::Event;
+
+mod migration;
+pub mod text;
+mod v1;
+
+pub const EXTENSION: &str = "json";
+pub const FILTER_NAME: &str = "Powerkey macro";
+pub const TEXT_EXTENSION: &str = "pkm";
+pub const TEXT_FILTER_NAME: &str = "Powerkey text macro";
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Format(serde_json::Error),
+    MissingVersion,
+    UnsupportedVersion(u64),
+    UnknownKey(String),
+    UnknownButton(String),
+    InvalidChord(String),
+    Text(text::ParseError),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Format(e) => write!(f, "Invalid macro file: {e}"),
+            Error::MissingVersion => write!(f, "Invalid macro file: missing format version"),
+            Error::UnsupportedVersion(version) => write!(
+                f,
+                "Macro file format version {version} is newer than this powerkey supports (version {})",
+                v1::VERSION
+            ),
+            Error::UnknownKey(key) => write!(f, "Invalid macro file: unknown key \"{key}\""),
+            Error::UnknownButton(button) => {
+                write!(f, "Invalid macro file: unknown mouse button \"{button}\"")
+            }
+            Error::InvalidChord(e) => write!(f, "Invalid macro file: {e}"),
+            Error::Text(e) => write!(f, "Invalid text macro: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    pub name: String,
+    pub created: SystemTime,
+    pub modified: SystemTime,
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl Header {
+    pub fn new(name: impl Into<String>) -> Self {
+        let now = SystemTime::now();
+        Self {
+            name: name.into(),
+            created: now,
+            modified: now,
+            metadata: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    pub header: Header,
+    pub events: Vec<Event>,
+}
+
+pub fn name_from_path(path: &Path) -> String {
+    path.file_stem()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+pub fn parse(content: &str, name: &str) -> Result<Document, Error> {
+    let value = serde_json::from_str(content).map_err(Error::Format)?;
+    let (header, events) = migration::upgrade(value, name)?.into_parts()?;
+    Ok(Document { header, events })
+}
+
+pub fn serialize(document: &Document) -> Result<String, Error> {
+    serde_json::to_string_pretty(&v1::Document::new(&document.header, &document.events))
+        .map_err(Error::Format)
+}
+
+/// Events copied out of a macro, either as JSON like a macro file or as text macro lines
+/// timed from `start`
+pub fn parse_events(content: &str, start: SystemTime) -> Result<Vec<Event>, Error> {
+    if content.trim_start().starts_with(['{', '[']) {
+        return parse(content, "").map(|document| document.events);
+    }
+    text::parse(content, start).map_err(Error::Text)
+}
+
+/// Text macros only carry metadata, the rest of their header is made up from the file
+fn is_text(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(TEXT_EXTENSION))
+}
+
+fn is_macro(path: &Path) -> bool {
+    is_text(path)
+        || path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case(EXTENSION))
+}
+
+/// Macro files found directly in `directory`, sorted by path
+pub fn list(directory: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(directory).map_err(Error::Io)? {
+        let path = entry.map_err(Error::Io)?.path();
+        if path.is_file() && is_macro(&path) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+pub fn load(path: &Path) -> Result<Document, Error> {
+    let content = std::fs::read_to_string(path).map_err(Error::Io)?;
+    let name = name_from_path(path);
+    if is_text(path) {
+        let header = Header {
+            metadata: text::parse_metadata(&content),
+            ..Header::new(name)
+        };
+        let events = text::parse(&content, header.created).map_err(Error::Text)?;
+        return Ok(Document { header, events });
+    }
+    parse(&content, &name)
+}
+
+pub fn save(path: &Path, document: &Document) -> Result<(), Error> {
+    let content = if is_text(path) {
+        text::print_metadata(&document.header.metadata) + &text::print(&document.events)
+    } else {
+        serialize(document)?
+    };
+    std::fs::write(path, content).map_err(Error::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rdev::{EventType, Key};
+
+    use super::*;
+    use crate::subscription::global_event::{EventKind, Input, chord::Chord};
+
+    fn at(millis: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn round_trips_current_format() {
+        let document = Document {
+            header: Header {
+                name: "login".into(),
+                created: at(1_000),
+                modified: at(2_000),
+                metadata: [("author".to_owned(), "me".to_owned())].into(),
+            },
+            events: vec![
+                Event::new(
+                    at(1_000),
+                    EventKind::FocusChange {
+                        window_title: "Notepad".into(),
+                    },
+                ),
+                Event::new(
+                    at(1_100),
+                    EventKind::Input(Input(EventType::KeyPress(Key::Unknown(42)))),
+                ),
+                Event::new(
+                    at(1_150),
+                    EventKind::MouseMoveInWindow {
+                        x: 640.0,
+                        y: 480.0,
+                        window_x: 40.5,
+                        window_y: 30.0,
+                    },
+                ),
+                Event::new(
+                    at(1_180),
+                    EventKind::Chord {
+                        modifiers: "Ctrl+Shift+s".parse::<Chord>().unwrap().modifiers,
+                        key: Key::KeyS,
+                    },
+                ),
+                Event::new(at(1_200), EventKind::Delay(Duration::from_millis(120))),
+                Event::new(at(1_300), EventKind::YieldFocus),
+            ],
+        };
+
+        let content = serialize(&document).unwrap();
+
+        assert_eq!(parse(&content, "ignored").unwrap(), document);
+    }
+
+    #[test]
+    fn migrates_bare_event_list() {
+        let content = r#"[
+            {"time":{"secs_since_epoch":10,"nanos_since_epoch":0},"kind":{"Input":{"KeyPress":"ShiftLeft"}}},
+            {"time":{"secs_since_epoch":11,"nanos_since_epoch":0},"kind":{"Delay":{"secs":0,"nanos":16000000}}},
+            {"time":{"secs_since_epoch":12,"nanos_since_epoch":0},"kind":{"FocusChange":{"window_title":"Notepad"}}},
+            {"time":{"secs_since_epoch":13,"nanos_since_epoch":0},"kind":"YieldFocus"}
+        ]"#;
+
+        let document = parse(content, "legacy").unwrap();
+
+        assert_eq!(document.header.name, "legacy");
+        assert_eq!(document.header.created, at(10_000));
+        assert_eq!(document.header.modified, at(13_000));
+        assert_eq!(
+            document
+                .events
+                .into_iter()
+                .map(|event| event.kind)
+                .collect::<Vec<_>>(),
+            [
+                EventKind::Input(Input(EventType::KeyPress(Key::ShiftLeft))),
+                EventKind::Delay(Duration::from_millis(16)),
+                EventKind::FocusChange {
+                    window_title: "Notepad".into()
+                },
+                EventKind::YieldFocus,
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_events_as_text_or_json() {
+        let events = vec![Event::new(at(0), EventKind::YieldFocus)];
+        let document = Document {
+            header: Header::new("copied"),
+            events: events.clone(),
+        };
+
+        assert_eq!(parse_events("yield\n", at(0)).unwrap(), events);
+        assert_eq!(
+            parse_events(&serialize(&document).unwrap(), at(5_000)).unwrap(),
+            events
+        );
+        assert!(matches!(
+            parse_events("{ yield", at(0)),
+            Err(Error::Format(_))
+        ));
+    }
+
+    #[test]
+    fn lists_only_macro_files() {
+        let directory = std::env::temp_dir().join(format!("powerkey-list-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("nested.json")).unwrap();
+        for file in ["b.pkm", "a.json", "notes.txt"] {
+            std::fs::write(directory.join(file), "").unwrap();
+        }
+
+        let paths = list(&directory);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            paths.unwrap(),
+            [directory.join("a.json"), directory.join("b.pkm")]
+        );
+    }
+
+    #[test]
+    fn keeps_the_metadata_of_text_macros() {
+        let path = std::env::temp_dir().join(format!("powerkey-text-{}.pkm", std::process::id()));
+        let document = Document {
+            header: Header {
+                metadata: [("hotkey".to_owned(), "Ctrl+F1".to_owned())].into(),
+                ..Header::new("ignored")
+            },
+            events: vec![Event::new(at(0), EventKind::YieldFocus)],
+        };
+
+        save(&path, &document).unwrap();
+        let loaded = load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.header.metadata, document.header.metadata);
+        assert_eq!(loaded.events.len(), 1);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let content = r#"{"version":99,"name":"","created":0,"modified":0,"events":[]}"#;
+
+        assert!(matches!(
+            parse(content, ""),
+            Err(Error::UnsupportedVersion(99))
+        ));
+    }
+}
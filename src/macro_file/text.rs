@@ -0,0 +1,530 @@
+//! Line oriented macro format meant to be written by hand and reviewed in diffs:
+//!
+//! ```text
+//! # hotkey: Ctrl+F1
+//! # Comments start with a hash
+//! focus "Notepad"
+//! press ControlLeft
+//! press KeyS
+//! release KeyS
+//! release ControlLeft
+//! chord Ctrl+Shift+KeyS
+//! wait 120ms
+//! type "hello"
+//! move 640 480 window 40 30
+//! mouse-press Left
+//! yield
+//! ```
+//!
+//! Comments of the form `# key: value` before the first command hold the macro metadata.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Write},
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    key_name::{button_from_name, button_to_name, key_from_name, key_to_name},
+    subscription::global_event::{Event, EventKind, Input, chord::Chord},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug)]
+enum TokenValue {
+    Word(String),
+    /// Decoded string
+    Quoted(String),
+}
+
+#[derive(Debug)]
+struct Token {
+    value: TokenValue,
+    column: usize,
+}
+
+struct Line {
+    number: usize,
+    tokens: std::vec::IntoIter<Token>,
+    end_column: usize,
+}
+
+impl Line {
+    fn error(&self, column: usize, message: impl Into<String>) -> ParseError {
+        ParseError {
+            line: self.number,
+            column,
+            message: message.into(),
+        }
+    }
+
+    fn next(&mut self, expected: &str) -> Result<Token, ParseError> {
+        self.tokens
+            .next()
+            .ok_or_else(|| self.error(self.end_column, format!("Expected {expected}")))
+    }
+
+    fn word(&mut self, expected: &str) -> Result<(String, usize), ParseError> {
+        let token = self.next(expected)?;
+        match token.value {
+            TokenValue::Word(word) => Ok((word, token.column)),
+            TokenValue::Quoted(_) => {
+                Err(self.error(token.column, format!("Expected {expected}, found a string")))
+            }
+        }
+    }
+
+    fn quoted(&mut self, expected: &str) -> Result<String, ParseError> {
+        let token = self.next(expected)?;
+        match token.value {
+            TokenValue::Quoted(string) => Ok(string),
+            TokenValue::Word(word) => Err(self.error(
+                token.column,
+                format!("Expected {expected} between double quotes, found \"{word}\""),
+            )),
+        }
+    }
+
+    fn number<T: std::str::FromStr>(&mut self, expected: &str) -> Result<T, ParseError> {
+        let (word, column) = self.word(expected)?;
+        word.parse()
+            .map_err(|_| self.error(column, format!("Invalid {expected} \"{word}\"")))
+    }
+
+    fn key(&mut self) -> Result<rdev::Key, ParseError> {
+        let (word, column) = self.word("a key name")?;
+        key_from_name(&word).ok_or_else(|| self.error(column, format!("Unknown key \"{word}\"")))
+    }
+
+    fn chord(&mut self) -> Result<Chord, ParseError> {
+        let (word, column) = self.word("a chord")?;
+        word.parse().map_err(|e: String| self.error(column, e))
+    }
+
+    fn button(&mut self) -> Result<rdev::Button, ParseError> {
+        let (word, column) = self.word("a mouse button")?;
+        button_from_name(&word)
+            .ok_or_else(|| self.error(column, format!("Unknown mouse button \"{word}\"")))
+    }
+
+    fn duration(&mut self) -> Result<Duration, ParseError> {
+        let (word, column) = self.word("a duration")?;
+        let invalid = || {
+            self.error(
+                column,
+                format!("Invalid duration \"{word}\", expected a number followed by us, ms or s"),
+            )
+        };
+        let unit_start = word
+            .find(|c: char| c.is_ascii_alphabetic())
+            .ok_or_else(invalid)?;
+        let (value, unit) = word.split_at(unit_start);
+        let value: f64 = value.parse().map_err(|_| invalid())?;
+        let seconds = match unit {
+            "us" => value / 1_000_000.0,
+            "ms" => value / 1_000.0,
+            "s" => value,
+            _ => return Err(invalid()),
+        };
+        Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+    }
+
+    fn end(&mut self) -> Result<(), ParseError> {
+        match self.tokens.next() {
+            Some(token) => Err(self.error(token.column, "Unexpected argument")),
+            None => Ok(()),
+        }
+    }
+}
+
+fn tokenize(number: usize, line: &str) -> Result<Line, ParseError> {
+    let error = |column, message: &str| ParseError {
+        line: number,
+        column,
+        message: message.into(),
+    };
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().zip(1..).peekable();
+    let mut end_column = 1;
+    while let Some((c, column)) = chars.next() {
+        end_column = column + 1;
+        if c.is_whitespace() {
+            continue;
+        }
+        if c == '#' {
+            break;
+        }
+        if c == '"' {
+            let mut string = String::new();
+            loop {
+                let Some((c, char_column)) = chars.next() else {
+                    return Err(error(column, "Unterminated string"));
+                };
+                end_column = char_column + 1;
+                let decoded = match c {
+                    '"' => break,
+                    '\\' => match chars.next() {
+                        Some(('n', _)) => '\n',
+                        Some(('t', _)) => '\t',
+                        Some(('"', _)) => '"',
+                        Some(('\\', _)) => '\\',
+                        Some((_, escape_column)) => {
+                            return Err(error(escape_column, "Unknown escape sequence"));
+                        }
+                        None => return Err(error(column, "Unterminated string")),
+                    },
+                    c => c,
+                };
+                string.push(decoded);
+            }
+            tokens.push(Token {
+                value: TokenValue::Quoted(string),
+                column,
+            });
+            continue;
+        }
+        let mut word = String::from(c);
+        while let Some((c, char_column)) = chars.next_if(|(c, _)| !c.is_whitespace()) {
+            word.push(c);
+            end_column = char_column + 1;
+        }
+        tokens.push(Token {
+            value: TokenValue::Word(word),
+            column,
+        });
+    }
+    Ok(Line {
+        number,
+        tokens: tokens.into_iter(),
+        end_column,
+    })
+}
+
+/// Event kind of a line, none for blank and comment lines
+fn parse_line(number: usize, line: &str) -> Result<Option<EventKind>, ParseError> {
+    let mut line = tokenize(number, line)?;
+    let Some(command) = line.tokens.next() else {
+        return Ok(None);
+    };
+    let TokenValue::Word(command_name) = command.value else {
+        return Err(line.error(command.column, "Expected a command"));
+    };
+    let input = |event_type| EventKind::Input(Input(event_type));
+    let kind = match command_name.as_str() {
+        "press" => input(rdev::EventType::KeyPress(line.key()?)),
+        "release" => input(rdev::EventType::KeyRelease(line.key()?)),
+        "mouse-press" => input(rdev::EventType::ButtonPress(line.button()?)),
+        "mouse-release" => input(rdev::EventType::ButtonRelease(line.button()?)),
+        "move" => {
+            let x = line.number("x coordinate")?;
+            let y = line.number("y coordinate")?;
+            match line.tokens.next() {
+                None => input(rdev::EventType::MouseMove { x, y }),
+                Some(Token {
+                    value: TokenValue::Word(word),
+                    ..
+                }) if word == "window" => EventKind::MouseMoveInWindow {
+                    x,
+                    y,
+                    window_x: line.number("window x coordinate")?,
+                    window_y: line.number("window y coordinate")?,
+                },
+                Some(token) => {
+                    return Err(line.error(token.column, "Expected \"window\" or nothing"));
+                }
+            }
+        }
+        "wheel" => input(rdev::EventType::Wheel {
+            delta_x: line.number("horizontal delta")?,
+            delta_y: line.number("vertical delta")?,
+        }),
+        "type" => EventKind::TypeText(line.quoted("the text to type")?),
+        "chord" => {
+            let Chord { modifiers, key } = line.chord()?;
+            EventKind::Chord { modifiers, key }
+        }
+        "wait" => EventKind::Delay(line.duration()?),
+        "focus" => EventKind::FocusChange {
+            window_title: line.quoted("a window title")?,
+        },
+        "yield" => EventKind::YieldFocus,
+        unknown => {
+            return Err(line.error(command.column, format!("Unknown command \"{unknown}\"")));
+        }
+    };
+    line.end()?;
+    Ok(Some(kind))
+}
+
+/// Event times start at `start` and move forward with each `wait`
+pub fn parse(source: &str, start: SystemTime) -> Result<Vec<Event>, ParseError> {
+    let mut events = Vec::new();
+    let mut time = start;
+    for (line, number) in source.lines().zip(1..) {
+        let Some(kind) = parse_line(number, line)? else {
+            continue;
+        };
+        if let EventKind::Delay(duration) = kind {
+            time += duration;
+        }
+        events.push(Event::new(time, kind));
+    }
+    Ok(events)
+}
+
+/// Metadata of the `# key: value` comments found before the first command
+pub fn parse_metadata(source: &str) -> BTreeMap<String, String> {
+    let mut metadata = BTreeMap::new();
+    for line in source.lines().map(str::trim) {
+        if line.is_empty() {
+            continue;
+        }
+        let Some(comment) = line.strip_prefix('#') else {
+            break;
+        };
+        let Some((key, value)) = comment.split_once(':') else {
+            continue;
+        };
+        let key = key.trim();
+        if !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            metadata.insert(key.to_owned(), value.trim().to_owned());
+        }
+    }
+    metadata
+}
+
+/// Event kind of a single line, as [`print_event`] writes it
+pub fn parse_event(line: &str) -> Result<EventKind, ParseError> {
+    parse_line(1, line)?.ok_or_else(|| ParseError {
+        line: 1,
+        column: 1,
+        message: "Expected a command".into(),
+    })
+}
+
+fn quote(string: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in string.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn print_duration(duration: Duration) -> String {
+    if duration.subsec_nanos().is_multiple_of(1_000_000) {
+        format!("{}ms", duration.as_millis())
+    } else {
+        format!("{}us", duration.as_micros())
+    }
+}
+
+pub fn print_event(kind: &EventKind) -> String {
+    match kind {
+        EventKind::Input(Input(event_type)) => match *event_type {
+            rdev::EventType::KeyPress(key) => format!("press {}", key_to_name(key)),
+            rdev::EventType::KeyRelease(key) => format!("release {}", key_to_name(key)),
+            rdev::EventType::ButtonPress(button) => {
+                format!("mouse-press {}", button_to_name(button))
+            }
+            rdev::EventType::ButtonRelease(button) => {
+                format!("mouse-release {}", button_to_name(button))
+            }
+            rdev::EventType::MouseMove { x, y } => format!("move {x} {y}"),
+            rdev::EventType::Wheel { delta_x, delta_y } => format!("wheel {delta_x} {delta_y}"),
+        },
+        EventKind::MouseMoveInWindow {
+            x,
+            y,
+            window_x,
+            window_y,
+        } => format!("move {x} {y} window {window_x} {window_y}"),
+        EventKind::TypeText(text) => format!("type {}", quote(text)),
+        EventKind::Chord { modifiers, key } => format!(
+            "chord {}",
+            Chord {
+                modifiers: *modifiers,
+                key: *key
+            }
+        ),
+        EventKind::FocusChange { window_title } => format!("focus {}", quote(window_title)),
+        EventKind::Delay(duration) => format!("wait {}", print_duration(*duration)),
+        EventKind::YieldFocus => "yield".into(),
+    }
+}
+
+/// Comment lines [`parse_metadata`] reads back, to put before the events
+pub fn print_metadata(metadata: &BTreeMap<String, String>) -> String {
+    let mut text = String::new();
+    for (key, value) in metadata {
+        writeln!(text, "# {key}: {}", value.replace('\n', " ")).unwrap();
+    }
+    text
+}
+
+pub fn print(events: &[Event]) -> String {
+    let mut text = String::new();
+    for event in events {
+        writeln!(text, "{}", print_event(&event.kind)).unwrap();
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use rdev::{EventType, Key};
+
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<EventKind> {
+        parse(source, SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .into_iter()
+            .map(|event| event.kind)
+            .collect()
+    }
+
+    fn error(source: &str) -> (usize, usize) {
+        let error = parse(source, SystemTime::UNIX_EPOCH).unwrap_err();
+        (error.line, error.column)
+    }
+
+    #[test]
+    fn parses_every_command() {
+        let source = r#"
+            # Save the document
+            focus "Untitled - Notepad"
+            press ControlLeft   # hold
+            press KeyS
+            release KeyS
+            release ControlLeft
+            wait 1.5s
+            mouse-press Left
+            mouse-release Unknown(8)
+            move 10 20.5
+            wheel 0 -1
+            yield
+        "#;
+
+        assert_eq!(
+            kinds(source),
+            [
+                EventKind::FocusChange {
+                    window_title: "Untitled - Notepad".into()
+                },
+                EventKind::Input(Input(EventType::KeyPress(Key::ControlLeft))),
+                EventKind::Input(Input(EventType::KeyPress(Key::KeyS))),
+                EventKind::Input(Input(EventType::KeyRelease(Key::KeyS))),
+                EventKind::Input(Input(EventType::KeyRelease(Key::ControlLeft))),
+                EventKind::Delay(Duration::from_millis(1500)),
+                EventKind::Input(Input(EventType::ButtonPress(rdev::Button::Left))),
+                EventKind::Input(Input(EventType::ButtonRelease(rdev::Button::Unknown(8)))),
+                EventKind::Input(Input(EventType::MouseMove { x: 10.0, y: 20.5 })),
+                EventKind::Input(Input(EventType::Wheel {
+                    delta_x: 0,
+                    delta_y: -1
+                })),
+                EventKind::YieldFocus,
+            ]
+        );
+    }
+
+    #[test]
+    fn types_any_text() {
+        assert_eq!(
+            kinds(r#"type "a!\nçé""#),
+            [EventKind::TypeText("a!\nçé".into())]
+        );
+    }
+
+    #[test]
+    fn waits_move_event_time_forward() {
+        let events = parse(
+            "press KeyA\nwait 20ms\nrelease KeyA",
+            SystemTime::UNIX_EPOCH,
+        )
+        .unwrap();
+
+        assert_eq!(
+            events.last().unwrap().time,
+            SystemTime::UNIX_EPOCH + Duration::from_millis(20)
+        );
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        assert_eq!(error("yield\n  jump"), (2, 3));
+        assert_eq!(error("press KeyQ\npress Nope"), (2, 7));
+        assert_eq!(error("press"), (1, 6));
+        assert_eq!(error("wait 12 ms"), (1, 6));
+        assert_eq!(error("yield now"), (1, 7));
+        assert_eq!(error("focus \"Notepad"), (1, 7));
+        assert_eq!(error("focus Notepad"), (1, 7));
+        assert_eq!(error("type \"ok\\q\""), (1, 10));
+        assert_eq!(error("move 1 2 screen"), (1, 10));
+        assert_eq!(error("move 1 2 window 3"), (1, 18));
+        assert_eq!(error("chord Ctrl+Hyper+KeyS"), (1, 7));
+    }
+
+    #[test]
+    fn parses_a_single_event() {
+        assert_eq!(
+            parse_event("wait 40ms").unwrap(),
+            EventKind::Delay(Duration::from_millis(40))
+        );
+        assert_eq!(parse_event(" # nothing").unwrap_err().column, 1);
+        assert_eq!(parse_event("release Nope").unwrap_err().column, 9);
+    }
+
+    #[test]
+    fn reads_metadata_from_leading_comments() {
+        let source = "# hotkey: Ctrl+F1\n\n# Logs in\n# author : me\npress KeyA\n# late: ignored\n";
+        let metadata = parse_metadata(source);
+
+        assert_eq!(
+            metadata,
+            [
+                ("author".to_owned(), "me".to_owned()),
+                ("hotkey".to_owned(), "Ctrl+F1".to_owned()),
+            ]
+            .into()
+        );
+        assert_eq!(
+            print_metadata(&metadata),
+            "# author: me\n# hotkey: Ctrl+F1\n"
+        );
+        assert_eq!(kinds(source).len(), 1);
+    }
+
+    #[test]
+    fn printed_text_parses_back() {
+        let source = "focus \"say \\\"hi\\\"\"\npress ShiftLeft\nwait 16ms\nrelease ShiftLeft\nwait 1500us\nmove 1.5 2\nmove 100 80 window 10 -4\ntype \"Hi \\\"you\\\"\"\nchord Ctrl+Alt+Delete\nyield\n";
+        let events = parse(source, SystemTime::UNIX_EPOCH).unwrap();
+
+        assert_eq!(print(&events), source);
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    key_name::{button_from_name, button_to_name, key_from_name, key_to_name},
+    macro_file::{Error, Header},
+    subscription::global_event::{self, Input, chord::Chord},
+};
+
+pub const VERSION: u64 = 1;
+
+/// Timestamps are milliseconds since the unix epoch and keys are stored by name,
+/// so the file does not depend on `SystemTime` or rdev serialization details.
+/// New event types can be added as new variants without bumping the version.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Document {
+    pub version: u64,
+    pub name: String,
+    pub created: u64,
+    pub modified: u64,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    pub events: Vec<Event>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Event {
+    pub time: u64,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    KeyPress {
+        key: String,
+    },
+    KeyRelease {
+        key: String,
+    },
+    ButtonPress {
+        button: String,
+    },
+    ButtonRelease {
+        button: String,
+    },
+    /// The window coordinates are only known for moves recorded relative to a window
+    MouseMove {
+        x: f64,
+        y: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        window_x: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        window_y: Option<f64>,
+    },
+    Wheel {
+        delta_x: i64,
+        delta_y: i64,
+    },
+    TypeText {
+        text: String,
+    },
+    /// Stored as written in the editor, like `Ctrl+Shift+KeyS`
+    Chord {
+        chord: String,
+    },
+    FocusChange {
+        window_title: String,
+    },
+    Delay {
+        milliseconds: u64,
+    },
+    YieldFocus,
+}
+
+pub fn to_millis(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+pub fn from_millis(millis: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_millis(millis)
+}
+
+impl Document {
+    pub fn new(header: &Header, events: &[global_event::Event]) -> Self {
+        Self {
+            version: VERSION,
+            name: header.name.clone(),
+            created: to_millis(header.created),
+            modified: to_millis(header.modified),
+            metadata: header.metadata.clone(),
+            events: events.iter().map(Event::from).collect(),
+        }
+    }
+
+    pub fn into_parts(self) -> Result<(Header, Vec<global_event::Event>), Error> {
+        let events = self
+            .events
+            .into_iter()
+            .map(global_event::Event::try_from)
+            .collect::<Result<_, _>>()?;
+        let header = Header {
+            name: self.name,
+            created: from_millis(self.created),
+            modified: from_millis(self.modified),
+            metadata: self.metadata,
+        };
+        Ok((header, events))
+    }
+}
+
+impl From<&global_event::Event> for Event {
+    fn from(event: &global_event::Event) -> Self {
+        let kind = match &event.kind {
+            global_event::EventKind::Input(Input(event_type)) => match *event_type {
+                rdev::EventType::KeyPress(key) => EventKind::KeyPress {
+                    key: key_to_name(key),
+                },
+                rdev::EventType::KeyRelease(key) => EventKind::KeyRelease {
+                    key: key_to_name(key),
+                },
+                rdev::EventType::ButtonPress(button) => EventKind::ButtonPress {
+                    button: button_to_name(button),
+                },
+                rdev::EventType::ButtonRelease(button) => EventKind::ButtonRelease {
+                    button: button_to_name(button),
+                },
+                rdev::EventType::MouseMove { x, y } => EventKind::MouseMove {
+                    x,
+                    y,
+                    window_x: None,
+                    window_y: None,
+                },
+                rdev::EventType::Wheel { delta_x, delta_y } => {
+                    EventKind::Wheel { delta_x, delta_y }
+                }
+            },
+            global_event::EventKind::MouseMoveInWindow {
+                x,
+                y,
+                window_x,
+                window_y,
+            } => EventKind::MouseMove {
+                x: *x,
+                y: *y,
+                window_x: Some(*window_x),
+                window_y: Some(*window_y),
+            },
+            global_event::EventKind::TypeText(text) => EventKind::TypeText { text: text.clone() },
+            global_event::EventKind::Chord { modifiers, key } => EventKind::Chord {
+                chord: Chord {
+                    modifiers: *modifiers,
+                    key: *key,
+                }
+                .to_string(),
+            },
+            global_event::EventKind::FocusChange { window_title } => EventKind::FocusChange {
+                window_title: window_title.clone(),
+            },
+            global_event::EventKind::Delay(duration) => EventKind::Delay {
+                milliseconds: duration.as_millis() as u64,
+            },
+            global_event::EventKind::YieldFocus => EventKind::YieldFocus,
+        };
+        Self {
+            time: to_millis(event.time),
+            kind,
+        }
+    }
+}
+
+impl TryFrom<Event> for global_event::Event {
+    type Error = Error;
+
+    fn try_from(event: Event) -> Result<Self, Self::Error> {
+        let key = |name: String| key_from_name(&name).ok_or(Error::UnknownKey(name));
+        let button = |name: String| button_from_name(&name).ok_or(Error::UnknownButton(name));
+        let input = |event_type| global_event::EventKind::Input(Input(event_type));
+        let kind = match event.kind {
+            EventKind::KeyPress { key: name } => input(rdev::EventType::KeyPress(key(name)?)),
+            EventKind::KeyRelease { key: name } => input(rdev::EventType::KeyRelease(key(name)?)),
+            EventKind::ButtonPress { button: name } => {
+                input(rdev::EventType::ButtonPress(button(name)?))
+            }
+            EventKind::ButtonRelease { button: name } => {
+                input(rdev::EventType::ButtonRelease(button(name)?))
+            }
+            EventKind::MouseMove {
+                x,
+                y,
+                window_x: Some(window_x),
+                window_y: Some(window_y),
+            } => global_event::EventKind::MouseMoveInWindow {
+                x,
+                y,
+                window_x,
+                window_y,
+            },
+            EventKind::MouseMove { x, y, .. } => input(rdev::EventType::MouseMove { x, y }),
+            EventKind::Wheel { delta_x, delta_y } => {
+                input(rdev::EventType::Wheel { delta_x, delta_y })
+            }
+            EventKind::TypeText { text } => global_event::EventKind::TypeText(text),
+            EventKind::Chord { chord } => {
+                let Chord { modifiers, key } = chord.parse().map_err(Error::InvalidChord)?;
+                global_event::EventKind::Chord { modifiers, key }
+            }
+            EventKind::FocusChange { window_title } => {
+                global_event::EventKind::FocusChange { window_title }
+            }
+            EventKind::Delay { milliseconds } => {
+                global_event::EventKind::Delay(Duration::from_millis(milliseconds))
+            }
+            EventKind::YieldFocus => global_event::EventKind::YieldFocus,
+        };
+        Ok(global_event::Event::new(from_millis(event.time), kind))
+    }
+}
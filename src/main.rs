@@ -1,6 +1,9 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod custom_widget;
+mod key_name;
+mod macro_file;
+mod platform;
 mod subscription;
 mod utils;
 mod window;
@@ -23,6 +26,7 @@ fn main() {
     )
     .theme(window::main::theme)
     .subscription(window::main::subscription)
+    .exit_on_close_request(false)
     .run_with(window::main::State::new)
     .unwrap();
 }
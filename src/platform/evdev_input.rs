@@ -0,0 +1,363 @@
+use std::{
+    fmt::Display,
+    sync::{Mutex, mpsc},
+    time::SystemTime,
+};
+
+use evdev::{
+    AttributeSet, AttributeSetRef, EventSummary, EventType, InputEvent, KeyCode,
+    uinput::VirtualDevice,
+};
+use log::{info, warn};
+use rdev::Key;
+
+use crate::platform::{Error, GrabCallback, InputBackend};
+
+const VIRTUAL_DEVICE_NAME: &str = "powerkey virtual input";
+
+/// rdev reports X11 keycodes on Linux, which are evdev codes shifted by 8.
+/// Unknown keys keep that convention so that recordings made with either backend stay compatible.
+const X11_KEYCODE_OFFSET: u32 = 8;
+
+const KEY_RELEASE: i32 = 0;
+const KEY_PRESS: i32 = 1;
+const KEY_REPEAT: i32 = 2;
+
+macro_rules! decl_keycodes {
+    ($($key:ident, $code:ident),* $(,)?) => {
+        fn key_code_from_key(key: Key) -> Option<KeyCode> {
+            match key {
+                $(
+                    Key::$key => Some(KeyCode::$code),
+                )*
+                Key::Unknown(code) => code
+                    .checked_sub(X11_KEYCODE_OFFSET)
+                    .and_then(|code| u16::try_from(code).ok())
+                    .map(KeyCode::new),
+                #[allow(unreachable_patterns, reason = "rdev may add more keys")]
+                _ => None,
+            }
+        }
+
+        fn key_from_key_code(code: KeyCode) -> Key {
+            $(
+                if code == KeyCode::$code {
+                    return Key::$key;
+                }
+            )*
+            Key::Unknown(code.0 as u32 + X11_KEYCODE_OFFSET)
+        }
+    };
+}
+
+#[rustfmt::skip]
+decl_keycodes!(
+    Alt, KEY_LEFTALT,
+    AltGr, KEY_RIGHTALT,
+    Backspace, KEY_BACKSPACE,
+    CapsLock, KEY_CAPSLOCK,
+    ControlLeft, KEY_LEFTCTRL,
+    ControlRight, KEY_RIGHTCTRL,
+    Delete, KEY_DELETE,
+    DownArrow, KEY_DOWN,
+    End, KEY_END,
+    Escape, KEY_ESC,
+    F1, KEY_F1,
+    F2, KEY_F2,
+    F3, KEY_F3,
+    F4, KEY_F4,
+    F5, KEY_F5,
+    F6, KEY_F6,
+    F7, KEY_F7,
+    F8, KEY_F8,
+    F9, KEY_F9,
+    F10, KEY_F10,
+    F11, KEY_F11,
+    F12, KEY_F12,
+    Home, KEY_HOME,
+    LeftArrow, KEY_LEFT,
+    MetaLeft, KEY_LEFTMETA,
+    MetaRight, KEY_RIGHTMETA,
+    PageDown, KEY_PAGEDOWN,
+    PageUp, KEY_PAGEUP,
+    Return, KEY_ENTER,
+    RightArrow, KEY_RIGHT,
+    ShiftLeft, KEY_LEFTSHIFT,
+    ShiftRight, KEY_RIGHTSHIFT,
+    Space, KEY_SPACE,
+    Tab, KEY_TAB,
+    UpArrow, KEY_UP,
+    PrintScreen, KEY_SYSRQ,
+    ScrollLock, KEY_SCROLLLOCK,
+    Pause, KEY_PAUSE,
+    NumLock, KEY_NUMLOCK,
+    BackQuote, KEY_GRAVE,
+    Num1, KEY_1,
+    Num2, KEY_2,
+    Num3, KEY_3,
+    Num4, KEY_4,
+    Num5, KEY_5,
+    Num6, KEY_6,
+    Num7, KEY_7,
+    Num8, KEY_8,
+    Num9, KEY_9,
+    Num0, KEY_0,
+    Minus, KEY_MINUS,
+    Equal, KEY_EQUAL,
+    KeyQ, KEY_Q,
+    KeyW, KEY_W,
+    KeyE, KEY_E,
+    KeyR, KEY_R,
+    KeyT, KEY_T,
+    KeyY, KEY_Y,
+    KeyU, KEY_U,
+    KeyI, KEY_I,
+    KeyO, KEY_O,
+    KeyP, KEY_P,
+    LeftBracket, KEY_LEFTBRACE,
+    RightBracket, KEY_RIGHTBRACE,
+    KeyA, KEY_A,
+    KeyS, KEY_S,
+    KeyD, KEY_D,
+    KeyF, KEY_F,
+    KeyG, KEY_G,
+    KeyH, KEY_H,
+    KeyJ, KEY_J,
+    KeyK, KEY_K,
+    KeyL, KEY_L,
+    SemiColon, KEY_SEMICOLON,
+    Quote, KEY_APOSTROPHE,
+    BackSlash, KEY_BACKSLASH,
+    IntlBackslash, KEY_102ND,
+    KeyZ, KEY_Z,
+    KeyX, KEY_X,
+    KeyC, KEY_C,
+    KeyV, KEY_V,
+    KeyB, KEY_B,
+    KeyN, KEY_N,
+    KeyM, KEY_M,
+    Comma, KEY_COMMA,
+    Dot, KEY_DOT,
+    Slash, KEY_SLASH,
+    Insert, KEY_INSERT,
+    KpReturn, KEY_KPENTER,
+    KpMinus, KEY_KPMINUS,
+    KpPlus, KEY_KPPLUS,
+    KpMultiply, KEY_KPASTERISK,
+    KpDivide, KEY_KPSLASH,
+    Kp0, KEY_KP0,
+    Kp1, KEY_KP1,
+    Kp2, KEY_KP2,
+    Kp3, KEY_KP3,
+    Kp4, KEY_KP4,
+    Kp5, KEY_KP5,
+    Kp6, KEY_KP6,
+    Kp7, KEY_KP7,
+    Kp8, KEY_KP8,
+    Kp9, KEY_KP9,
+    KpDelete, KEY_KPDOT,
+    Function, KEY_FN,
+);
+
+fn input_error(e: impl Display) -> Error {
+    Error::Grab(e.to_string())
+}
+
+/// The virtual device is left out, it only replays what the grab forwards and what is simulated
+fn is_keyboard(name: Option<&str>, keys: Option<&AttributeSetRef<KeyCode>>) -> bool {
+    name != Some(VIRTUAL_DEVICE_NAME)
+        && keys
+            .is_some_and(|keys| keys.contains(KeyCode::KEY_A) && keys.contains(KeyCode::KEY_ENTER))
+}
+
+/// Auto-repeat is reported as more presses, like other backends do
+fn event_type(code: KeyCode, value: i32) -> Option<rdev::EventType> {
+    let key = key_from_key_code(code);
+    match value {
+        KEY_RELEASE => Some(rdev::EventType::KeyRelease(key)),
+        KEY_PRESS | KEY_REPEAT => Some(rdev::EventType::KeyPress(key)),
+        _ => None,
+    }
+}
+
+fn key_event(code: KeyCode, value: i32) -> InputEvent {
+    InputEvent::new(EventType::KEY.0, code.0, value)
+}
+
+/// Input backend talking to the kernel directly, it works without any display server
+/// but requires read access to `/dev/input/event*` and write access to `/dev/uinput`
+pub struct EvdevInputBackend {
+    virtual_device: Mutex<VirtualDevice>,
+}
+
+impl EvdevInputBackend {
+    pub fn new() -> Result<Self, Error> {
+        // Keyboard keys only, declaring buttons would make the device look like a pointer
+        let mut keys = AttributeSet::<KeyCode>::new();
+        for code in KeyCode::KEY_ESC.0..=KeyCode::KEY_MICMUTE.0 {
+            keys.insert(KeyCode::new(code));
+        }
+        keys.insert(KeyCode::KEY_FN);
+        let virtual_device = VirtualDevice::builder()
+            .and_then(|builder| builder.name(VIRTUAL_DEVICE_NAME).with_keys(&keys))
+            .and_then(|builder| builder.build())
+            .map_err(|e| Error::Simulate(e.to_string()))?;
+        Ok(Self {
+            virtual_device: Mutex::new(virtual_device),
+        })
+    }
+
+    fn emit(&self, event: InputEvent) -> std::io::Result<()> {
+        self.virtual_device.lock().unwrap().emit(&[event])
+    }
+}
+
+impl InputBackend for EvdevInputBackend {
+    fn grab(&self, mut callback: GrabCallback) -> Result<(), Error> {
+        let (event_tx, event_rx) = mpsc::channel();
+        let mut keyboard_count = 0;
+        for (path, mut device) in evdev::enumerate() {
+            if !is_keyboard(device.name(), device.supported_keys()) {
+                continue;
+            }
+            if let Err(e) = device.grab() {
+                warn!("Could not grab {}: {e}", path.display());
+                continue;
+            }
+            info!("Grabbed keyboard {}", path.display());
+            keyboard_count += 1;
+            let event_tx = event_tx.clone();
+            std::thread::spawn(move || {
+                loop {
+                    let events = match device.fetch_events() {
+                        Ok(events) => events,
+                        Err(e) => {
+                            warn!("Stopped reading {}: {e}", path.display());
+                            return;
+                        }
+                    };
+                    for event in events {
+                        if event_tx.send(event).is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+        drop(event_tx);
+
+        if keyboard_count == 0 {
+            return Err(Error::Grab("No keyboard could be grabbed".into()));
+        }
+
+        for event in event_rx {
+            let EventSummary::Key(_, code, value) = event.destructure() else {
+                continue;
+            };
+            let Some(event_type) = event_type(code, value) else {
+                continue;
+            };
+            let forwarded = callback(rdev::Event {
+                time: SystemTime::now(),
+                name: None,
+                event_type,
+            });
+            if forwarded.is_some() {
+                self.emit(key_event(code, value)).map_err(input_error)?;
+            }
+        }
+
+        Err(Error::Grab(
+            "All grabbed keyboards were disconnected".into(),
+        ))
+    }
+
+    /// Simulated events go straight to the virtual device, which is not grabbed
+    fn grab_sees_simulated_input(&self) -> bool {
+        false
+    }
+
+    fn simulate(&self, event: &rdev::EventType) -> Result<(), Error> {
+        let (key, value) = match event {
+            rdev::EventType::KeyPress(key) => (key, KEY_PRESS),
+            rdev::EventType::KeyRelease(key) => (key, KEY_RELEASE),
+            _ => return Err(Error::Unsupported("mouse injection through uinput")),
+        };
+        let code = key_code_from_key(*key)
+            .ok_or_else(|| Error::Simulate(format!("No evdev key code for {key:?}")))?;
+        self.emit(key_event(code, value))
+            .map_err(|e| Error::Simulate(e.to_string()))
+    }
+
+    fn type_char(&self, _c: char) -> Result<(), Error> {
+        Err(Error::Unsupported("Unicode input through uinput"))
+    }
+
+    fn typed_char_events(&self, _c: char) -> Result<Vec<rdev::EventType>, Error> {
+        Err(Error::Unsupported("Unicode input through uinput"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(codes: &[KeyCode]) -> AttributeSet<KeyCode> {
+        let mut keys = AttributeSet::new();
+        for code in codes {
+            keys.insert(*code);
+        }
+        keys
+    }
+
+    #[test]
+    fn grabs_physical_keyboards_only() {
+        let keyboard = || {
+            Some(keys(&[
+                KeyCode::KEY_A,
+                KeyCode::KEY_ENTER,
+                KeyCode::KEY_ESC,
+            ]))
+        };
+        let devices = [
+            (Some("AT Translated Set 2 keyboard"), keyboard()),
+            (Some(VIRTUAL_DEVICE_NAME), keyboard()),
+            (Some("Power Button"), Some(keys(&[KeyCode::KEY_POWER]))),
+            (Some("Logitech USB Optical Mouse"), None),
+            (None, keyboard()),
+        ];
+
+        let grabbed = devices
+            .iter()
+            .map(|(name, keys)| is_keyboard(*name, keys.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(grabbed, [true, false, false, false, true]);
+    }
+
+    #[test]
+    fn maps_key_codes_to_the_keys_rdev_reports() {
+        assert_eq!(key_code_from_key(Key::KeyA), Some(KeyCode::KEY_A));
+        assert_eq!(key_from_key_code(KeyCode::KEY_LEFTSHIFT), Key::ShiftLeft);
+
+        let mute = Key::Unknown(KeyCode::KEY_MUTE.0 as u32 + X11_KEYCODE_OFFSET);
+        assert_eq!(key_from_key_code(KeyCode::KEY_MUTE), mute);
+        assert_eq!(key_code_from_key(mute), Some(KeyCode::KEY_MUTE));
+        assert_eq!(key_code_from_key(Key::Unknown(3)), None);
+    }
+
+    #[test]
+    fn reports_auto_repeat_as_presses() {
+        let events =
+            [KEY_PRESS, KEY_REPEAT, KEY_RELEASE, 3].map(|value| event_type(KeyCode::KEY_A, value));
+
+        assert_eq!(
+            events,
+            [
+                Some(rdev::EventType::KeyPress(Key::KeyA)),
+                Some(rdev::EventType::KeyPress(Key::KeyA)),
+                Some(rdev::EventType::KeyRelease(Key::KeyA)),
+                None,
+            ]
+        );
+    }
+}
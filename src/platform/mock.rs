@@ -0,0 +1,201 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use futures::future::BoxFuture;
+
+use crate::platform::{
+    Backend, Clock, Error, FocusCallback, GrabCallback, InputBackend, WindowBackend,
+};
+
+/// Everything the player did to the outside world, in order
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Simulate(rdev::EventType),
+    TypeChar(char),
+    Focus(String),
+}
+
+type Journal = Arc<Mutex<Vec<Action>>>;
+
+/// Clock that only moves when something sleeps or when a test advances it
+pub struct MockClock {
+    now: Mutex<SystemTime>,
+}
+
+impl MockClock {
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        self.advance(duration);
+        Box::pin(std::future::ready(()))
+    }
+}
+
+struct MockInputBackend {
+    journal: Journal,
+    failing_event: Arc<Mutex<Option<rdev::EventType>>>,
+    unicode_input: Arc<Mutex<bool>>,
+    simulating_around_grab: Arc<Mutex<bool>>,
+}
+
+impl InputBackend for MockInputBackend {
+    fn grab(&self, _callback: GrabCallback) -> Result<(), Error> {
+        Err(Error::Unsupported("grabbing from the mock backend"))
+    }
+
+    fn grab_sees_simulated_input(&self) -> bool {
+        !*self.simulating_around_grab.lock().unwrap()
+    }
+
+    fn simulate(&self, event: &rdev::EventType) -> Result<(), Error> {
+        if *self.failing_event.lock().unwrap() == Some(*event) {
+            return Err(Error::Simulate(format!(
+                "{event:?} rejected by the mock backend"
+            )));
+        }
+        self.journal.lock().unwrap().push(Action::Simulate(*event));
+        Ok(())
+    }
+
+    fn type_char(&self, c: char) -> Result<(), Error> {
+        if !*self.unicode_input.lock().unwrap() {
+            return Err(Error::Unsupported("Unicode input from the mock backend"));
+        }
+        self.journal.lock().unwrap().push(Action::TypeChar(c));
+        Ok(())
+    }
+
+    /// A stroke of a key named after `c`, standing for the packets real backends inject
+    fn typed_char_events(&self, c: char) -> Result<Vec<rdev::EventType>, Error> {
+        if !*self.unicode_input.lock().unwrap() {
+            return Err(Error::Unsupported("Unicode input from the mock backend"));
+        }
+        let key = rdev::Key::Unknown(c.into());
+        Ok(vec![
+            rdev::EventType::KeyPress(key),
+            rdev::EventType::KeyRelease(key),
+        ])
+    }
+}
+
+type WindowOrigins = Arc<Mutex<HashMap<String, (f64, f64)>>>;
+
+struct MockWindowBackend {
+    journal: Journal,
+    focused_window_title: Arc<Mutex<Option<String>>>,
+    window_origins: WindowOrigins,
+}
+
+impl WindowBackend for MockWindowBackend {
+    fn watch_focus(&self, _callback: FocusCallback) -> Result<(), Error> {
+        Err(Error::Unsupported("watching focus from the mock backend"))
+    }
+
+    fn focused_window_title(&self) -> Option<String> {
+        self.focused_window_title.lock().unwrap().clone()
+    }
+
+    fn focus_window_by_title(&self, title: &str) -> Result<(), Error> {
+        self.journal
+            .lock()
+            .unwrap()
+            .push(Action::Focus(title.to_owned()));
+        *self.focused_window_title.lock().unwrap() = Some(title.to_owned());
+        Ok(())
+    }
+
+    fn window_origin(&self, title: &str) -> Option<(f64, f64)> {
+        self.window_origins.lock().unwrap().get(title).cloned()
+    }
+}
+
+/// In-memory backend recording injected input and focus changes
+pub struct Mock {
+    pub backend: Backend,
+    pub clock: Arc<MockClock>,
+    journal: Journal,
+    focused_window_title: Arc<Mutex<Option<String>>>,
+    failing_event: Arc<Mutex<Option<rdev::EventType>>>,
+    unicode_input: Arc<Mutex<bool>>,
+    simulating_around_grab: Arc<Mutex<bool>>,
+    window_origins: WindowOrigins,
+}
+
+impl Mock {
+    pub fn new(focused_window_title: &str) -> Self {
+        let journal = Journal::default();
+        let focused_window_title = Arc::new(Mutex::new(Some(focused_window_title.to_owned())));
+        let failing_event = Arc::new(Mutex::new(None));
+        let unicode_input = Arc::new(Mutex::new(false));
+        let simulating_around_grab = Arc::new(Mutex::new(false));
+        let window_origins = WindowOrigins::default();
+        let clock = Arc::new(MockClock {
+            now: Mutex::new(SystemTime::UNIX_EPOCH),
+        });
+        Self {
+            backend: Backend {
+                input: Arc::new(MockInputBackend {
+                    journal: journal.clone(),
+                    failing_event: failing_event.clone(),
+                    unicode_input: unicode_input.clone(),
+                    simulating_around_grab: simulating_around_grab.clone(),
+                }),
+                window: Arc::new(MockWindowBackend {
+                    journal: journal.clone(),
+                    focused_window_title: focused_window_title.clone(),
+                    window_origins: window_origins.clone(),
+                }),
+                clock: clock.clone(),
+            },
+            clock,
+            journal,
+            focused_window_title,
+            failing_event,
+            unicode_input,
+            simulating_around_grab,
+            window_origins,
+        }
+    }
+
+    /// Puts the top left corner of the window titled `title` at `origin`
+    pub fn place_window(&self, title: &str, origin: (f64, f64)) {
+        self.window_origins
+            .lock()
+            .unwrap()
+            .insert(title.to_owned(), origin);
+    }
+
+    /// Makes simulating `event` fail, as if the OS rejected the injection
+    pub fn fail_on(&self, event: rdev::EventType) {
+        *self.failing_event.lock().unwrap() = Some(event);
+    }
+
+    /// Makes the input backend type characters itself instead of refusing to
+    pub fn enable_unicode_input(&self) {
+        *self.unicode_input.lock().unwrap() = true;
+    }
+
+    /// Makes simulated input bypass the grab, like uinput devices do
+    pub fn simulate_around_grab(&self) {
+        *self.simulating_around_grab.lock().unwrap() = true;
+    }
+
+    pub fn journal(&self) -> Vec<Action> {
+        self.journal.lock().unwrap().clone()
+    }
+
+    pub fn focused_window_title(&self) -> Option<String> {
+        self.focused_window_title.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    fmt::{Debug, Display},
+    sync::{Arc, OnceLock},
+    time::{Duration, SystemTime},
+};
+
+use futures::future::BoxFuture;
+
+#[cfg(target_os = "linux")]
+mod evdev_input;
+#[cfg(test)]
+pub mod mock;
+mod rdev_input;
+#[cfg(windows)]
+mod win32;
+#[cfg(target_os = "linux")]
+mod x11;
+
+pub type GrabCallback = Box<dyn FnMut(rdev::Event) -> Option<rdev::Event> + Send>;
+pub type FocusCallback = Box<dyn FnMut(String) + Send>;
+
+#[derive(Debug)]
+pub enum Error {
+    Unsupported(&'static str),
+    Grab(String),
+    Simulate(String),
+    Window(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Unsupported(feature) => write!(f, "{feature} is not supported on this platform"),
+            Error::Grab(e) => write!(f, "Could not grab input: {e}"),
+            Error::Simulate(e) => write!(f, "Could not simulate input: {e}"),
+            Error::Window(e) => write!(f, "Window error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Captures and injects keyboard and mouse input
+pub trait InputBackend: Send + Sync {
+    /// Blocks the calling thread and routes every input event through `callback`.
+    /// Events for which `callback` returns `None` are not forwarded to the system.
+    fn grab(&self, callback: GrabCallback) -> Result<(), Error>;
+
+    /// Whether simulated events go through the callback of [`Self::grab`] like user input
+    fn grab_sees_simulated_input(&self) -> bool;
+
+    fn simulate(&self, event: &rdev::EventType) -> Result<(), Error>;
+
+    /// Types `c` without going through the keyboard layout.
+    /// Backends unable to inject Unicode return [`Error::Unsupported`].
+    fn type_char(&self, c: char) -> Result<(), Error>;
+
+    /// Events [`Self::grab`] sees when [`Self::type_char`] types `c`, in order.
+    /// Backends unable to inject Unicode return [`Error::Unsupported`].
+    fn typed_char_events(&self, c: char) -> Result<Vec<rdev::EventType>, Error>;
+}
+
+/// Tracks and changes the focused window
+pub trait WindowBackend: Send + Sync {
+    /// Blocks the calling thread and calls `callback` with the title of each newly focused window.
+    fn watch_focus(&self, callback: FocusCallback) -> Result<(), Error>;
+
+    fn focused_window_title(&self) -> Option<String>;
+
+    fn focus_window_by_title(&self, title: &str) -> Result<(), Error>;
+
+    /// Screen coordinates of the top left corner of the window titled `title`
+    fn window_origin(&self, title: &str) -> Option<(f64, f64)>;
+}
+
+/// Source of time for the state machines, so that tests do not depend on the wall clock
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            smol::Timer::after(duration).await;
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct Backend {
+    pub input: Arc<dyn InputBackend>,
+    pub window: Arc<dyn WindowBackend>,
+    pub clock: Arc<dyn Clock>,
+}
+
+impl Debug for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Backend").finish_non_exhaustive()
+    }
+}
+
+impl Backend {
+    /// Built on first use then shared, so that the listener and the player do not each
+    /// open their own devices
+    pub fn native() -> Self {
+        static NATIVE: OnceLock<Backend> = OnceLock::new();
+        NATIVE
+            .get_or_init(|| Self {
+                input: native_input_backend(),
+                window: native_window_backend(),
+                clock: Arc::new(SystemClock),
+            })
+            .clone()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn native_input_backend() -> Arc<dyn InputBackend> {
+    // X11 hooks are not available on Wayland and headless sessions, talk to the kernel instead
+    let use_evdev = match std::env::var("POWERKEY_INPUT_BACKEND").as_deref() {
+        Ok("evdev") => true,
+        Ok("rdev") => false,
+        _ => std::env::var_os("WAYLAND_DISPLAY").is_some() || std::env::var_os("DISPLAY").is_none(),
+    };
+    if use_evdev {
+        match evdev_input::EvdevInputBackend::new() {
+            Ok(backend) => return Arc::new(backend),
+            Err(e) => log::warn!("evdev input backend is not available, using rdev instead: {e}"),
+        }
+    }
+    Arc::new(rdev_input::RdevInputBackend)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn native_input_backend() -> Arc<dyn InputBackend> {
+    Arc::new(rdev_input::RdevInputBackend)
+}
+
+#[cfg(windows)]
+fn native_window_backend() -> Arc<dyn WindowBackend> {
+    Arc::new(win32::Win32WindowBackend)
+}
+
+#[cfg(target_os = "linux")]
+fn native_window_backend() -> Arc<dyn WindowBackend> {
+    match x11::X11WindowBackend::connect() {
+        Ok(backend) => Arc::new(backend),
+        Err(e) => {
+            log::warn!("X11 is not available, window focus is disabled: {e}");
+            Arc::new(UnsupportedWindowBackend)
+        }
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn native_window_backend() -> Arc<dyn WindowBackend> {
+    Arc::new(UnsupportedWindowBackend)
+}
+
+/// Used on platforms without window support: focus is never reported nor changed
+#[cfg(not(windows))]
+struct UnsupportedWindowBackend;
+
+#[cfg(not(windows))]
+impl WindowBackend for UnsupportedWindowBackend {
+    fn watch_focus(&self, _callback: FocusCallback) -> Result<(), Error> {
+        Err(Error::Unsupported("window focus tracking"))
+    }
+
+    fn focused_window_title(&self) -> Option<String> {
+        None
+    }
+
+    fn focus_window_by_title(&self, _title: &str) -> Result<(), Error> {
+        Err(Error::Unsupported("window activation"))
+    }
+
+    fn window_origin(&self, _title: &str) -> Option<(f64, f64)> {
+        None
+    }
+}
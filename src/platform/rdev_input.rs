@@ -0,0 +1,37 @@
+use crate::platform::{Error, GrabCallback, InputBackend};
+
+pub struct RdevInputBackend;
+
+impl InputBackend for RdevInputBackend {
+    fn grab(&self, callback: GrabCallback) -> Result<(), Error> {
+        rdev::grab(callback).map_err(|e| Error::Grab(format!("{e:?}")))
+    }
+
+    fn grab_sees_simulated_input(&self) -> bool {
+        true
+    }
+
+    fn simulate(&self, event: &rdev::EventType) -> Result<(), Error> {
+        rdev::simulate(event).map_err(|e| Error::Simulate(format!("{e:?}")))
+    }
+
+    #[cfg(windows)]
+    fn type_char(&self, c: char) -> Result<(), Error> {
+        crate::platform::win32::type_char(c)
+    }
+
+    #[cfg(not(windows))]
+    fn type_char(&self, _c: char) -> Result<(), Error> {
+        Err(Error::Unsupported("Unicode input through rdev"))
+    }
+
+    #[cfg(windows)]
+    fn typed_char_events(&self, c: char) -> Result<Vec<rdev::EventType>, Error> {
+        Ok(crate::platform::win32::typed_char_events(c))
+    }
+
+    #[cfg(not(windows))]
+    fn typed_char_events(&self, _c: char) -> Result<Vec<rdev::EventType>, Error> {
+        Err(Error::Unsupported("Unicode input through rdev"))
+    }
+}
@@ -0,0 +1,165 @@
+use std::ptr::null_mut;
+
+use log::info;
+use windows::Win32::{
+    Foundation::{HWND, RECT},
+    UI::{
+        Accessibility::{HWINEVENTHOOK, SetWinEventHook},
+        Input::KeyboardAndMouse::{
+            INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYEVENTF_KEYUP, KEYEVENTF_UNICODE,
+            SendInput, VIRTUAL_KEY, VK_PACKET,
+        },
+        WindowsAndMessaging::{
+            EVENT_OBJECT_FOCUS, FindWindowW, GetForegroundWindow, GetMessageA, GetWindowRect,
+            GetWindowTextLengthA, GetWindowTextW, SetForegroundWindow, WINEVENT_OUTOFCONTEXT,
+            WINEVENT_SKIPOWNPROCESS,
+        },
+    },
+};
+
+use crate::platform::{Error, FocusCallback, WindowBackend};
+
+pub struct Win32WindowBackend;
+
+fn get_window_title_from_hwnd(window: HWND) -> Result<String, std::string::FromUtf16Error> {
+    unsafe {
+        let len = GetWindowTextLengthA(window) + 1; // + 1 for null terminator
+        let mut title = vec![0u16; len as usize];
+        GetWindowTextW(window, title.as_mut_slice());
+        windows_strings::PWSTR::from_raw(title.as_mut_ptr()).to_string()
+    }
+}
+
+/// Sends `c` as Unicode key strokes, which Windows delivers whatever the keyboard layout.
+/// Both halves of a surrogate pair go down before either goes up, so that applications
+/// receive them as consecutive characters.
+pub fn type_char(c: char) -> Result<(), Error> {
+    let mut buffer = [0; 2];
+    let units: &[u16] = c.encode_utf16(&mut buffer);
+    let inputs = [KEYEVENTF_UNICODE, KEYEVENTF_UNICODE | KEYEVENTF_KEYUP]
+        .into_iter()
+        .flat_map(|flags| {
+            units.iter().map(move |unit| INPUT {
+                r#type: INPUT_KEYBOARD,
+                Anonymous: INPUT_0 {
+                    ki: KEYBDINPUT {
+                        wVk: VIRTUAL_KEY(0),
+                        wScan: *unit,
+                        dwFlags: flags,
+                        time: 0,
+                        dwExtraInfo: 0,
+                    },
+                },
+            })
+        })
+        .collect::<Vec<_>>();
+    let sent = unsafe { SendInput(&inputs, std::mem::size_of::<INPUT>() as i32) };
+    if sent as usize != inputs.len() {
+        return Err(Error::Simulate(format!(
+            "Only {sent} of {} inputs typing {c:?} were sent",
+            inputs.len()
+        )));
+    }
+    Ok(())
+}
+
+/// What the keyboard hook sees of [`type_char`]: the Unicode strokes come as `VK_PACKET`
+/// presses, one for each UTF-16 unit of `c`, then as many releases
+pub fn typed_char_events(c: char) -> Vec<rdev::EventType> {
+    let key = rdev::Key::Unknown(VK_PACKET.0.into());
+    std::iter::repeat_n(rdev::EventType::KeyPress(key), c.len_utf16())
+        .chain(std::iter::repeat_n(
+            rdev::EventType::KeyRelease(key),
+            c.len_utf16(),
+        ))
+        .collect()
+}
+
+impl WindowBackend for Win32WindowBackend {
+    fn watch_focus(&self, callback: FocusCallback) -> Result<(), Error> {
+        static mut FOCUS_CALLBACK: Option<FocusCallback> = None;
+
+        unsafe extern "system" fn hook_callback(
+            _hwineventhook: HWINEVENTHOOK,
+            event: u32,
+            hwnd: HWND,
+            _idobject: i32,
+            _idchild: i32,
+            _ideventthread: u32,
+            _dwmseventtime: u32,
+        ) {
+            unsafe {
+                fn is_window_title_ok<S: AsRef<str>>(title: S) -> bool {
+                    #[allow(clippy::match_like_matches_macro, reason = "More will be added later")]
+                    match title.as_ref() {
+                        "" => false,
+                        _ => true,
+                    }
+                }
+
+                if event == EVENT_OBJECT_FOCUS
+                    && let Ok(window_title) = get_window_title_from_hwnd(hwnd)
+                    && is_window_title_ok(&window_title)
+                {
+                    let callback_ptr = &raw mut FOCUS_CALLBACK;
+                    if let Some(callback) = &mut *callback_ptr {
+                        callback(window_title);
+                    }
+                }
+            }
+        }
+
+        unsafe {
+            FOCUS_CALLBACK = Some(callback);
+            let hook = SetWinEventHook(
+                EVENT_OBJECT_FOCUS,
+                EVENT_OBJECT_FOCUS,
+                None,
+                Some(hook_callback),
+                0,
+                0,
+                WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+            );
+            if hook.is_invalid() {
+                return Err(Error::Window(
+                    "Could not start window focus listener".into(),
+                ));
+            }
+            info!("Focus hook setup");
+            GetMessageA(null_mut(), None, 0, 0)
+                .ok()
+                .map_err(|e| Error::Window(e.to_string()))
+        }
+    }
+
+    fn focused_window_title(&self) -> Option<String> {
+        unsafe { get_window_title_from_hwnd(GetForegroundWindow()).ok() }
+    }
+
+    fn focus_window_by_title(&self, title: &str) -> Result<(), Error> {
+        unsafe {
+            if let Ok(window) = FindWindowW(
+                windows_strings::PCWSTR::null(),
+                &windows_strings::HSTRING::from(title),
+            ) {
+                SetForegroundWindow(window)
+                    .ok()
+                    .map_err(|e| Error::Window(e.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn window_origin(&self, title: &str) -> Option<(f64, f64)> {
+        unsafe {
+            let window = FindWindowW(
+                windows_strings::PCWSTR::null(),
+                &windows_strings::HSTRING::from(title),
+            )
+            .ok()?;
+            let mut rect = RECT::default();
+            GetWindowRect(window, &mut rect).ok()?;
+            Some((rect.left.into(), rect.top.into()))
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use std::fmt::Display;
+
+use log::info;
+use x11rb::{
+    connection::Connection,
+    protocol::{
+        Event,
+        xproto::{
+            AtomEnum, ChangeWindowAttributesAux, ClientMessageEvent, ConnectionExt, EventMask,
+            Window,
+        },
+    },
+    rust_connection::RustConnection,
+};
+
+use crate::platform::{Error, FocusCallback, WindowBackend};
+
+x11rb::atom_manager! {
+    Atoms: AtomsCookie {
+        _NET_ACTIVE_WINDOW,
+        _NET_CLIENT_LIST,
+        _NET_WM_NAME,
+        UTF8_STRING,
+    }
+}
+
+/// Source indication of `_NET_ACTIVE_WINDOW` requests, 2 means the request comes from a pager
+/// which makes window managers honor it without focus stealing prevention
+const ACTIVATION_SOURCE_PAGER: u32 = 2;
+
+fn window_error(e: impl Display) -> Error {
+    Error::Window(e.to_string())
+}
+
+/// Window backend relying on the EWMH hints maintained by the window manager
+pub struct X11WindowBackend {
+    connection: RustConnection,
+    root: Window,
+    atoms: Atoms,
+}
+
+impl X11WindowBackend {
+    pub fn connect() -> Result<Self, Error> {
+        let (connection, root, atoms) = connect()?;
+        Ok(Self {
+            connection,
+            root,
+            atoms,
+        })
+    }
+}
+
+fn connect() -> Result<(RustConnection, Window, Atoms), Error> {
+    let (connection, screen_num) = x11rb::connect(None).map_err(window_error)?;
+    let root = connection.setup().roots[screen_num].root;
+    let atoms = Atoms::new(&connection)
+        .map_err(window_error)?
+        .reply()
+        .map_err(window_error)?;
+    Ok((connection, root, atoms))
+}
+
+fn active_window(connection: &RustConnection, root: Window, atoms: &Atoms) -> Option<Window> {
+    connection
+        .get_property(
+            false,
+            root,
+            atoms._NET_ACTIVE_WINDOW,
+            AtomEnum::WINDOW,
+            0,
+            1,
+        )
+        .ok()?
+        .reply()
+        .ok()?
+        .value32()?
+        .next()
+        .filter(|window| *window != x11rb::NONE)
+}
+
+fn window_title(connection: &RustConnection, atoms: &Atoms, window: Window) -> Option<String> {
+    let net_wm_name = connection
+        .get_property(
+            false,
+            window,
+            atoms._NET_WM_NAME,
+            atoms.UTF8_STRING,
+            0,
+            u32::MAX,
+        )
+        .ok()?
+        .reply()
+        .ok()?;
+    if !net_wm_name.value.is_empty() {
+        return String::from_utf8(net_wm_name.value).ok();
+    }
+
+    // Fallback for clients that only set the ICCCM name
+    let wm_name = connection
+        .get_property(
+            false,
+            window,
+            AtomEnum::WM_NAME,
+            AtomEnum::STRING,
+            0,
+            u32::MAX,
+        )
+        .ok()?
+        .reply()
+        .ok()?;
+    Some(String::from_utf8_lossy(&wm_name.value).into_owned())
+}
+
+fn client_windows(connection: &RustConnection, root: Window, atoms: &Atoms) -> Vec<Window> {
+    let client_list = connection
+        .get_property(
+            false,
+            root,
+            atoms._NET_CLIENT_LIST,
+            AtomEnum::WINDOW,
+            0,
+            u32::MAX,
+        )
+        .ok()
+        .and_then(|cookie| cookie.reply().ok());
+    if let Some(client_list) = client_list
+        && let Some(windows) = client_list.value32()
+    {
+        return windows.collect();
+    }
+
+    // No window manager advertising its clients, use top level windows instead
+    connection
+        .query_tree(root)
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .map(|tree| tree.children)
+        .unwrap_or_default()
+}
+
+impl X11WindowBackend {
+    fn find_window(&self, title: &str) -> Option<Window> {
+        client_windows(&self.connection, self.root, &self.atoms)
+            .into_iter()
+            .find(|window| {
+                window_title(&self.connection, &self.atoms, *window).as_deref() == Some(title)
+            })
+    }
+}
+
+impl WindowBackend for X11WindowBackend {
+    fn watch_focus(&self, mut callback: FocusCallback) -> Result<(), Error> {
+        // Dedicated connection so that waiting for events does not race with the queries
+        let (connection, root, atoms) = connect()?;
+        connection
+            .change_window_attributes(
+                root,
+                &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+            )
+            .map_err(window_error)?
+            .check()
+            .map_err(window_error)?;
+        info!("Focus hook setup");
+
+        let mut current_window = None;
+        loop {
+            let event = connection.wait_for_event().map_err(window_error)?;
+            let Event::PropertyNotify(property_event) = event else {
+                continue;
+            };
+            if property_event.atom != atoms._NET_ACTIVE_WINDOW {
+                continue;
+            }
+            let window = active_window(&connection, root, &atoms);
+            if window.is_none() || window == current_window {
+                continue;
+            }
+            current_window = window;
+            if let Some(window_title) = window.and_then(|w| window_title(&connection, &atoms, w))
+                && !window_title.is_empty()
+            {
+                callback(window_title);
+            }
+        }
+    }
+
+    fn focused_window_title(&self) -> Option<String> {
+        let window = active_window(&self.connection, self.root, &self.atoms)?;
+        window_title(&self.connection, &self.atoms, window)
+    }
+
+    fn focus_window_by_title(&self, title: &str) -> Result<(), Error> {
+        let Some(window) = self.find_window(title) else {
+            return Err(Error::Window(format!("No window titled \"{title}\"")));
+        };
+
+        let event = ClientMessageEvent::new(
+            32,
+            window,
+            self.atoms._NET_ACTIVE_WINDOW,
+            [ACTIVATION_SOURCE_PAGER, x11rb::CURRENT_TIME, 0, 0, 0],
+        );
+        self.connection
+            .send_event(
+                false,
+                self.root,
+                EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+                event,
+            )
+            .map_err(window_error)?;
+        self.connection.flush().map_err(window_error)
+    }
+
+    fn window_origin(&self, title: &str) -> Option<(f64, f64)> {
+        let window = self.find_window(title)?;
+        // Window positions are relative to their parent, which is a frame when reparented
+        let origin = self
+            .connection
+            .translate_coordinates(window, self.root, 0, 0)
+            .ok()?
+            .reply()
+            .ok()?;
+        Some((origin.dst_x.into(), origin.dst_y.into()))
+    }
+}
@@ -0,0 +1,244 @@
+use std::{collections::HashSet, fmt::Display, str::FromStr};
+
+use rdev::Key;
+use serde::{Deserialize, Serialize};
+
+use crate::key_name::{key_from_name, key_to_name, keystroke_for_char};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Modifiers {
+    pub control: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub meta: bool,
+}
+
+impl Modifiers {
+    /// Modifier held down by `key`, if it is a modifier key
+    fn of_key(key: Key) -> Option<Self> {
+        let modifiers = Self::default();
+        match key {
+            Key::ControlLeft | Key::ControlRight => Some(Self {
+                control: true,
+                ..modifiers
+            }),
+            Key::Alt | Key::AltGr => Some(Self {
+                alt: true,
+                ..modifiers
+            }),
+            Key::ShiftLeft | Key::ShiftRight => Some(Self {
+                shift: true,
+                ..modifiers
+            }),
+            Key::MetaLeft | Key::MetaRight => Some(Self {
+                meta: true,
+                ..modifiers
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn is_modifier(key: Key) -> bool {
+        Self::of_key(key).is_some()
+    }
+
+    /// Keys holding down these modifiers, in the order a chord presses them
+    fn keys(self) -> impl DoubleEndedIterator<Item = Key> {
+        [
+            (self.control, Key::ControlLeft),
+            (self.alt, Key::Alt),
+            (self.shift, Key::ShiftLeft),
+            (self.meta, Key::MetaLeft),
+        ]
+        .into_iter()
+        .filter_map(|(held, key)| held.then_some(key))
+    }
+
+    fn union(self, other: Self) -> Self {
+        Self {
+            control: self.control || other.control,
+            alt: self.alt || other.alt,
+            shift: self.shift || other.shift,
+            meta: self.meta || other.meta,
+        }
+    }
+}
+
+/// A key pressed while holding modifiers, like `Ctrl+Alt+F5`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Chord {
+    pub modifiers: Modifiers,
+    pub key: Key,
+}
+
+impl Chord {
+    /// Modifiers pressed, then the key pressed and released, then the modifiers
+    /// released in reverse order
+    pub fn key_events(&self) -> Vec<rdev::EventType> {
+        self.modifiers
+            .keys()
+            .map(rdev::EventType::KeyPress)
+            .chain([
+                rdev::EventType::KeyPress(self.key),
+                rdev::EventType::KeyRelease(self.key),
+            ])
+            .chain(self.modifiers.keys().rev().map(rdev::EventType::KeyRelease))
+            .collect()
+    }
+}
+
+impl Display for Chord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (held, name) in [
+            (self.modifiers.control, "Ctrl"),
+            (self.modifiers.alt, "Alt"),
+            (self.modifiers.shift, "Shift"),
+            (self.modifiers.meta, "Meta"),
+        ] {
+            if held {
+                write!(f, "{name}+")?;
+            }
+        }
+        write!(f, "{}", key_to_name(self.key))
+    }
+}
+
+impl FromStr for Chord {
+    type Err = String;
+
+    /// Modifiers and key are separated by `+`. The key is either a key name or a single
+    /// character, so `Ctrl+Alt+F5`, `ctrl+shift+KeyS` and `Alt+s` are all valid.
+    fn from_str(chord: &str) -> Result<Self, Self::Err> {
+        let mut parts = chord.split('+').map(str::trim).collect::<Vec<_>>();
+        let Some(key_name) = parts.pop().filter(|key_name| !key_name.is_empty()) else {
+            return Err(format!("Missing key in chord \"{chord}\""));
+        };
+        let mut modifiers = Modifiers::default();
+        for modifier in parts {
+            let held = match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => &mut modifiers.control,
+                "alt" => &mut modifiers.alt,
+                "shift" => &mut modifiers.shift,
+                "meta" | "super" | "win" | "cmd" => &mut modifiers.meta,
+                _ => {
+                    return Err(format!(
+                        "Unknown modifier \"{modifier}\" in chord \"{chord}\""
+                    ));
+                }
+            };
+            *held = true;
+        }
+        let mut chars = key_name.chars();
+        let key = match (chars.next(), chars.next()) {
+            (Some(c), None) => keystroke_for_char(c.to_ascii_lowercase()).map(|(key, _)| key),
+            _ => key_from_name(key_name),
+        }
+        .ok_or_else(|| format!("Unknown key \"{key_name}\" in chord \"{chord}\""))?;
+        if Modifiers::is_modifier(key) {
+            return Err(format!(
+                "Chord \"{chord}\" must end with a non modifier key"
+            ));
+        }
+        Ok(Self { modifiers, key })
+    }
+}
+
+/// Follows held modifiers across key events to recognize chords as they are typed
+#[derive(Debug, Default)]
+pub struct ChordTracker {
+    held_modifier_keys: HashSet<Key>,
+}
+
+impl ChordTracker {
+    /// Returns the chord completed by `event_type`, when it presses a non modifier key
+    pub fn on_event(&mut self, event_type: &rdev::EventType) -> Option<Chord> {
+        match *event_type {
+            rdev::EventType::KeyPress(key) if Modifiers::is_modifier(key) => {
+                self.held_modifier_keys.insert(key);
+                None
+            }
+            rdev::EventType::KeyRelease(key) => {
+                self.held_modifier_keys.remove(&key);
+                None
+            }
+            rdev::EventType::KeyPress(key) => Some(Chord {
+                modifiers: self
+                    .held_modifier_keys
+                    .iter()
+                    .filter_map(|key| Modifiers::of_key(*key))
+                    .fold(Modifiers::default(), Modifiers::union),
+                key,
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rdev::EventType;
+
+    use super::*;
+
+    #[test]
+    fn parses_and_prints_chords() {
+        let chord: Chord = "ctrl + alt+F5".parse().unwrap();
+
+        assert_eq!(
+            chord,
+            Chord {
+                modifiers: Modifiers {
+                    control: true,
+                    alt: true,
+                    ..Default::default()
+                },
+                key: Key::F5
+            }
+        );
+        assert_eq!(chord.to_string(), "Ctrl+Alt+F5");
+        assert_eq!(
+            "Shift+s".parse::<Chord>().unwrap().to_string(),
+            "Shift+KeyS"
+        );
+        assert!("Ctrl+".parse::<Chord>().is_err());
+        assert!("Hyper+F5".parse::<Chord>().is_err());
+        assert!("Ctrl+ShiftLeft".parse::<Chord>().is_err());
+    }
+
+    #[test]
+    fn presses_modifiers_around_the_key() {
+        let chord: Chord = "Shift+Ctrl+s".parse().unwrap();
+
+        assert_eq!(
+            chord.key_events(),
+            [
+                EventType::KeyPress(Key::ControlLeft),
+                EventType::KeyPress(Key::ShiftLeft),
+                EventType::KeyPress(Key::KeyS),
+                EventType::KeyRelease(Key::KeyS),
+                EventType::KeyRelease(Key::ShiftLeft),
+                EventType::KeyRelease(Key::ControlLeft),
+            ]
+        );
+    }
+
+    #[test]
+    fn tracks_modifiers_held_on_either_side() {
+        let mut tracker = ChordTracker::default();
+
+        assert_eq!(
+            tracker.on_event(&EventType::KeyPress(Key::ControlRight)),
+            None
+        );
+        assert_eq!(tracker.on_event(&EventType::KeyPress(Key::Alt)), None);
+        assert_eq!(
+            tracker.on_event(&EventType::KeyPress(Key::F5)),
+            Some("Ctrl+Alt+F5".parse().unwrap())
+        );
+        tracker.on_event(&EventType::KeyRelease(Key::Alt));
+        assert_eq!(
+            tracker.on_event(&EventType::KeyPress(Key::F5)),
+            Some("Ctrl+F5".parse().unwrap())
+        );
+    }
+}
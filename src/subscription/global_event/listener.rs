@@ -1,12 +1,14 @@
 use std::{
-    collections::VecDeque,
-    ptr::null_mut,
+    collections::{HashSet, VecDeque},
     time::{Duration, SystemTime},
 };
 
 use crate::{
-    subscription::global_event::{Event, EventKind, Input},
-    utils::get_window_title_from_hwnd,
+    platform::Backend,
+    subscription::global_event::{
+        Event, EventKind, Input,
+        chord::{Chord, ChordTracker},
+    },
 };
 use iced::{
     futures::{
@@ -15,37 +17,52 @@ use iced::{
     },
     stream,
 };
-use log::{error, info};
-use windows::Win32::{
-    Foundation,
-    UI::{
-        Accessibility::{HWINEVENTHOOK, SetWinEventHook},
-        WindowsAndMessaging::{
-            EVENT_OBJECT_FOCUS, GetMessageA, WINEVENT_OUTOFCONTEXT, WINEVENT_SKIPOWNPROCESS,
-        },
-    },
-};
+use log::{error, info, warn};
+
+const PANIC_HOTKEY_ENV: &str = "POWERKEY_PANIC_HOTKEY";
+const DEFAULT_PANIC_HOTKEY: &str = "Ctrl+Alt+Escape";
+/// Recorded pointer moves are at least this far apart, the ones in between are dropped
+const MOUSE_MOVE_INTERVAL: Duration = Duration::from_millis(50);
 
 #[derive(Default, Clone, Debug)]
 pub enum Mode {
     #[default]
     Disabled,
-    Listen,
+    /// Mouse events always reach the system, they are only recorded when `record_mouse` is set
+    Listen { record_mouse: bool },
     Grab {
         simulated_events: VecDeque<rdev::EventType>,
     },
+    /// Waits for a chord, which is swallowed and sent as [`Message::KeyCaptured`],
+    /// then goes back to disabled
+    CaptureKey,
 }
 
 #[derive(Debug)]
-struct State {
+pub(super) struct State {
     mode: Mode,
     current_window_title: Option<String>,
+    /// Origin of the focused window, looked up by the first move recorded since the focus
+    /// or the mode changed rather than by every move
+    current_window_origin: Option<Option<(f64, f64)>>,
+    backend: Backend,
+    hotkeys: HashSet<Chord>,
+    /// Watched in every mode, even while grabbing, to get out of a misbehaving playback
+    panic_hotkey: Chord,
+    chord_tracker: ChordTracker,
+    /// Keys whose press triggered a hotkey, their release must not reach the system either
+    swallowed_keys: HashSet<rdev::Key>,
+    /// Latest pointer move held back because it came too soon after the previous one
+    pending_mouse_move: Option<(SystemTime, f64, f64)>,
+    last_mouse_move_time: Option<SystemTime>,
 }
 
 #[derive(Debug)]
 pub enum Command {
     ChangeMode(Mode),
     SetNextEventsToBeIgnoredByGrab(Vec<rdev::EventType>),
+    /// Chords watched while disabled, replacing the previous ones
+    SetHotkeys(HashSet<Chord>),
 }
 
 #[derive(Debug)]
@@ -54,19 +71,58 @@ pub enum Message {
     ModeJustSet(Mode),
     SetNextEventsToBeIgnoredByGrabDone,
     Event(Event),
+    HotkeyPressed(Chord),
+    PanicHotkeyPressed,
+    KeyCaptured(Chord),
+}
+
+/// `POWERKEY_PANIC_HOTKEY` if set to a valid chord, otherwise `Ctrl+Alt+Escape`
+fn panic_hotkey() -> Chord {
+    if let Ok(hotkey) = std::env::var(PANIC_HOTKEY_ENV) {
+        match hotkey.parse() {
+            Ok(hotkey) => return hotkey,
+            Err(e) => warn!("Ignoring {PANIC_HOTKEY_ENV}: {e}"),
+        }
+    }
+    DEFAULT_PANIC_HOTKEY.parse().unwrap()
 }
 
 impl State {
-    fn new() -> Self {
+    pub(super) fn new(backend: Backend) -> Self {
         Self {
             mode: Mode::Disabled,
             current_window_title: None,
+            current_window_origin: None,
+            backend,
+            hotkeys: Default::default(),
+            panic_hotkey: panic_hotkey(),
+            chord_tracker: Default::default(),
+            swallowed_keys: Default::default(),
+            pending_mouse_move: None,
+            last_mouse_move_time: None,
         }
     }
 
-    async fn handle_command(&mut self, command: Command, mut message_sender: Sender<Message>) {
+    pub(super) async fn handle_command(
+        &mut self,
+        command: Command,
+        mut message_sender: Sender<Message>,
+    ) {
         match command {
-            Command::ChangeMode(mode) => {
+            Command::ChangeMode(mut mode) => {
+                // Nothing simulated would come through the grab to use the list up
+                if let Mode::Grab { simulated_events } = &mut mode
+                    && !self.backend.input.grab_sees_simulated_input()
+                {
+                    simulated_events.clear();
+                }
+                // The move held back is where the pointer ends up at the end of the recording
+                if let Mode::Listen { .. } = self.mode {
+                    self.send_pending_mouse_move(&mut message_sender).await;
+                }
+                self.pending_mouse_move = None;
+                self.current_window_origin = None;
+                self.last_mouse_move_time = None;
                 message_sender
                     .send(Message::ModeJustSet(mode.clone()))
                     .await // TODO: Use lightweight message instead of copying vec in grab
@@ -82,27 +138,38 @@ impl State {
                     );
                     return;
                 };
-                for event in events.into_iter().rev() {
-                    simulated_events.push_front(event);
+                if self.backend.input.grab_sees_simulated_input() {
+                    for event in events.into_iter().rev() {
+                        simulated_events.push_front(event);
+                    }
                 }
                 message_sender
                     .send(Message::SetNextEventsToBeIgnoredByGrabDone)
                     .await
                     .unwrap();
             }
+            Command::SetHotkeys(hotkeys) => {
+                info!("Listener: hotkeys set to {hotkeys:?}");
+                self.hotkeys = hotkeys;
+            }
         }
     }
 
-    async fn on_focus_event(&mut self, window_title: String, mut message_sender: Sender<Message>) {
+    pub(super) async fn on_focus_event(
+        &mut self,
+        window_title: String,
+        mut message_sender: Sender<Message>,
+    ) {
         if self
             .current_window_title
             .as_ref()
             .is_none_or(|title| *title != window_title)
         {
             self.current_window_title = Some(window_title.clone());
+            self.current_window_origin = None;
             message_sender
                 .send(Message::Event(Event {
-                    time: SystemTime::now(),
+                    time: self.backend.clock.now(),
                     kind: EventKind::FocusChange { window_title },
                 }))
                 .await
@@ -110,27 +177,75 @@ impl State {
         }
     }
 
-    async fn on_key_event(
+    pub(super) async fn on_key_event(
         &mut self,
         event: rdev::Event,
         mut message_sender: Sender<Message>,
     ) -> Option<rdev::Event> {
-        // We don't care about mouse events
-        if let rdev::Event {
-            event_type:
-                rdev::EventType::Wheel { .. }
-                | rdev::EventType::MouseMove { .. }
-                | rdev::EventType::ButtonPress(_)
-                | rdev::EventType::ButtonRelease(_),
-            ..
-        } = event
+        // Mouse events are never grabbed, nor part of chords
+        if let rdev::EventType::Wheel { .. }
+        | rdev::EventType::MouseMove { .. }
+        | rdev::EventType::ButtonPress(_)
+        | rdev::EventType::ButtonRelease(_) = event.event_type
         {
+            if let Mode::Listen { record_mouse: true } = self.mode {
+                self.record_mouse_event(&event, message_sender).await;
+            }
             return Some(event);
         }
 
+        if let Mode::Listen { .. } = self.mode {
+            self.send_pending_mouse_move(&mut message_sender).await;
+        }
+
+        let chord = self.chord_tracker.on_event(&event.event_type);
+        if let rdev::EventType::KeyRelease(key) = event.event_type
+            && self.swallowed_keys.remove(&key)
+        {
+            return None;
+        }
+
+        let is_simulated = matches!(
+            &self.mode,
+            Mode::Grab { simulated_events }
+                if simulated_events.front() == Some(&event.event_type)
+        );
+        // Auto-repeat of a swallowed press, which would trigger its hotkey over and over
+        if let rdev::EventType::KeyPress(key) = event.event_type
+            && !is_simulated
+            && self.swallowed_keys.contains(&key)
+        {
+            return None;
+        }
+        if !is_simulated && chord == Some(self.panic_hotkey) {
+            warn!("Listener: panic hotkey pressed");
+            self.swallowed_keys.insert(self.panic_hotkey.key);
+            self.mode = Mode::Disabled;
+            message_sender
+                .send(Message::ModeJustSet(Mode::Disabled))
+                .await
+                .unwrap();
+            message_sender
+                .send(Message::PanicHotkeyPressed)
+                .await
+                .unwrap();
+            return None;
+        }
+
         match &mut self.mode {
-            Mode::Disabled => Some(event),
-            Mode::Listen => {
+            Mode::Disabled => {
+                if let Some(chord) = chord.filter(|chord| self.hotkeys.contains(chord)) {
+                    info!("Listener: hotkey {chord} pressed");
+                    self.swallowed_keys.insert(chord.key);
+                    message_sender
+                        .send(Message::HotkeyPressed(chord))
+                        .await
+                        .unwrap();
+                    return None;
+                }
+                Some(event)
+            }
+            Mode::Listen { .. } => {
                 message_sender
                     .send(Message::Event(Event::new(
                         event.time,
@@ -140,11 +255,29 @@ impl State {
                     .unwrap();
                 Some(event)
             }
+            Mode::CaptureKey => {
+                let Some(chord) = chord else {
+                    return Some(event);
+                };
+                info!("Listener: {chord} captured");
+                self.swallowed_keys.insert(chord.key);
+                self.mode = Mode::Disabled;
+                message_sender
+                    .send(Message::ModeJustSet(Mode::Disabled))
+                    .await
+                    .unwrap();
+                message_sender
+                    .send(Message::KeyCaptured(chord))
+                    .await
+                    .unwrap();
+                None
+            }
             Mode::Grab { simulated_events } => {
-                if let Some(simulated_event) = simulated_events.front() {
-                    if event.event_type == *simulated_event {
-                        return Some(event);
-                    }
+                if let Some(simulated_event) = simulated_events.front()
+                    && event.event_type == *simulated_event
+                {
+                    simulated_events.pop_front();
+                    return Some(event);
                 }
                 message_sender
                     .send(Message::Event(Event::new(
@@ -157,6 +290,71 @@ impl State {
             }
         }
     }
+
+    /// Moves are coalesced, so that a wiggle does not fill the macro with hundreds of them
+    async fn record_mouse_event(
+        &mut self,
+        event: &rdev::Event,
+        mut message_sender: Sender<Message>,
+    ) {
+        if let rdev::EventType::MouseMove { x, y } = event.event_type {
+            let is_too_soon = self.last_mouse_move_time.is_some_and(|last_time| {
+                event.time.duration_since(last_time).unwrap_or_default() < MOUSE_MOVE_INTERVAL
+            });
+            if is_too_soon {
+                self.pending_mouse_move = Some((event.time, x, y));
+                return;
+            }
+            self.send_pending_mouse_move(&mut message_sender).await;
+            self.send_mouse_move(event.time, x, y, &mut message_sender)
+                .await;
+            return;
+        }
+        // Buttons and wheel apply where the pointer rests, which may be a held back move
+        self.send_pending_mouse_move(&mut message_sender).await;
+        message_sender
+            .send(Message::Event(Event::new(
+                event.time,
+                EventKind::Input(Input(event.event_type)),
+            )))
+            .await
+            .unwrap();
+    }
+
+    async fn send_pending_mouse_move(&mut self, message_sender: &mut Sender<Message>) {
+        if let Some((time, x, y)) = self.pending_mouse_move.take() {
+            self.send_mouse_move(time, x, y, message_sender).await;
+        }
+    }
+
+    /// Sends a move relative to the focused window, or only absolute when it is unknown
+    async fn send_mouse_move(
+        &mut self,
+        time: SystemTime,
+        x: f64,
+        y: f64,
+        message_sender: &mut Sender<Message>,
+    ) {
+        self.last_mouse_move_time = Some(time);
+        let origin = *self.current_window_origin.get_or_insert_with(|| {
+            self.current_window_title
+                .as_deref()
+                .and_then(|window_title| self.backend.window.window_origin(window_title))
+        });
+        let kind = match origin {
+            Some((origin_x, origin_y)) => EventKind::MouseMoveInWindow {
+                x,
+                y,
+                window_x: x - origin_x,
+                window_y: y - origin_y,
+            },
+            None => EventKind::Input(Input(rdev::EventType::MouseMove { x, y })),
+        };
+        message_sender
+            .send(Message::Event(Event::new(time, kind)))
+            .await
+            .unwrap();
+    }
 }
 
 pub fn subscription() -> impl Stream<Item = Message> {
@@ -172,11 +370,13 @@ pub fn subscription() -> impl Stream<Item = Message> {
             Focus(String),
         }
 
-        let mut listener = State::new();
+        let backend = Backend::native();
+        let mut listener = State::new(backend.clone());
         let (command_tx, command_rx) = channel(100);
         let (mut grab_event_tx, grab_event_rx) = channel(100);
+        let input_backend = backend.input.clone();
         std::thread::spawn(move || {
-            rdev::grab(move |event| {
+            if let Err(e) = input_backend.grab(Box::new(move |event| {
                 let (response_sender, response_rx) = oneshot::channel();
                 grab_event_tx
                     .try_send(GrabMessage {
@@ -187,69 +387,19 @@ pub fn subscription() -> impl Stream<Item = Message> {
                 response_rx
                     .recv_timeout(Duration::from_millis(200))
                     .unwrap_or(Some(event))
-            })
-            .unwrap()
-        });
-
-        let (focus_event_tx, focus_event_rx) = channel(100);
-        std::thread::spawn(move || unsafe {
-            static mut FOCUS_EVENT_TX: Option<Sender<String>> = None;
-            FOCUS_EVENT_TX = Some(focus_event_tx);
-            unsafe extern "system" fn callback(
-                _hwineventhook: HWINEVENTHOOK,
-                event: u32,
-                hwnd: Foundation::HWND,
-                _idobject: i32,
-                _idchild: i32,
-                _ideventthread: u32,
-                _dwmseventtime: u32,
-            ) {
-                unsafe {
-                    fn is_window_title_ok<S: AsRef<str>>(title: S) -> bool {
-                        #[allow(
-                            clippy::match_like_matches_macro,
-                            reason = "More will be added later"
-                        )]
-                        match title.as_ref() {
-                            "" => false,
-                            _ => true,
-                        }
-                    }
-
-                    if event == EVENT_OBJECT_FOCUS {
-                        if let Ok(window_title) = get_window_title_from_hwnd(hwnd) {
-                            if is_window_title_ok(&window_title) {
-                                let sender_ptr = &raw mut FOCUS_EVENT_TX;
-                                if let Some(sender) = &mut *sender_ptr {
-                                    // const CLASS_MAX_LEN: usize = 256;
-                                    // let mut title = vec![0u16; CLASS_MAX_LEN];
-                                    // GetClassNameW(hwnd, title.as_mut_slice());
-                                    // let class_name =
-                                    //     windows_strings::PWSTR::from_raw(title.as_mut_ptr())
-                                    //         .to_string();
-                                    // info!("{window_title}: class name: {class_name:?}");
-                                    sender.try_send(window_title).unwrap();
-                                }
-                            }
-                        }
-                    }
-                }
+            })) {
+                error!("Input listener stopped, hotkeys and recording are unavailable: {e}");
             }
+        });
 
-            let hook = SetWinEventHook(
-                EVENT_OBJECT_FOCUS,
-                EVENT_OBJECT_FOCUS,
-                None,
-                Some(callback),
-                0,
-                0,
-                WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
-            );
-            if hook.is_invalid() {
-                panic!("Could not start window focus listener");
+        let (mut focus_event_tx, focus_event_rx) = channel(100);
+        let window_backend = backend.window.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = window_backend.watch_focus(Box::new(move |window_title| {
+                focus_event_tx.try_send(window_title).unwrap();
+            })) {
+                error!("Could not start window focus listener: {e}");
             }
-            info!("Focus hook setup");
-            GetMessageA(null_mut(), None, 0, 0).unwrap();
         });
 
         output.send(Message::Ready(command_tx)).await.unwrap();
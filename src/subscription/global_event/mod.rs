@@ -2,8 +2,13 @@ use std::time::{Duration, SystemTime};
 
 use serde::{Deserialize, Serialize};
 
+use self::chord::Modifiers;
+
+pub mod chord;
 pub mod listener;
 pub mod player;
+#[cfg(test)]
+mod tests;
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Event {
@@ -23,7 +28,24 @@ pub struct Input(pub rdev::EventType);
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub enum EventKind {
     Input(Input),
-    FocusChange { window_title: String },
+    /// Pointer move with both its screen coordinates and its coordinates relative to
+    /// the window focused when it was recorded
+    MouseMoveInWindow {
+        x: f64,
+        y: f64,
+        window_x: f64,
+        window_y: f64,
+    },
+    /// Text typed character by character, whatever the keyboard layout when the backend allows it
+    TypeText(String),
+    /// Key pressed while holding modifiers, played as the whole press and release sequence
+    Chord {
+        modifiers: Modifiers,
+        key: rdev::Key,
+    },
+    FocusChange {
+        window_title: String,
+    },
     Delay(Duration),
     YieldFocus,
 }
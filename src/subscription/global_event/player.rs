@@ -1,5 +1,6 @@
 use std::{
     collections::{BTreeSet, VecDeque},
+    fmt::Display,
     time::{Duration, SystemTime},
 };
 
@@ -12,34 +13,200 @@ use iced::{
 };
 use itertools::Itertools;
 use log::{error, info, trace, warn};
-use smol::{Timer, stream::StreamExt};
+use smol::stream::StreamExt;
 
 use crate::{
-    subscription::global_event::{Event, EventKind, Input, listener},
-    utils::{get_focused_window_title, set_focused_window_by_title},
+    key_name::key_events_for_char,
+    platform::{self, Backend},
+    subscription::global_event::{Event, EventKind, Input, chord::Chord, listener},
 };
 
+#[derive(Debug)]
 pub enum Message {
     SenderReady(Sender<Command>),
     PlaybackJustStarted,
-    JustPlayed { index: usize },
+    /// Answers [`Command::PausePlayback`], only when the playback could be paused
+    PlaybackJustPaused,
+    PlaybackJustResumed,
+    JustPlayed {
+        index: usize,
+    },
+    /// The playback reached the end of the events and starts over, `iteration` counts from 1
+    IterationJustStarted {
+        iteration: u32,
+        total: Option<u32>,
+    },
+    /// The playback paused before playing the event at `index`
+    BreakpointHit {
+        index: usize,
+    },
     PlaybackDone,
 }
 
+/// Pause the system needs between two simulated inputs to register both
+const RECORDED_INPUT_GAP: Duration = Duration::from_millis(16);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    /// Recorded delays, scaled by the playback speed
+    #[default]
+    Recorded,
+    /// No delay at all, for bulk data entry
+    AsFastAsPossible,
+    /// Recorded delays are ignored, every input is followed by the same delay
+    FixedDelay,
+}
+
+impl Timing {
+    pub const ALL: [Timing; 3] = [
+        Timing::Recorded,
+        Timing::AsFastAsPossible,
+        Timing::FixedDelay,
+    ];
+}
+
+impl Display for Timing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Timing::Recorded => write!(f, "Recorded timing"),
+            Timing::AsFastAsPossible => write!(f, "As fast as possible"),
+            Timing::FixedDelay => write!(f, "Fixed delay"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    #[default]
+    Times,
+    For,
+    UntilStopped,
+}
+
+impl Repeat {
+    pub const ALL: [Repeat; 3] = [Repeat::Times, Repeat::For, Repeat::UntilStopped];
+}
+
+impl Display for Repeat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Repeat::Times => write!(f, "Repeat a number of times"),
+            Repeat::For => write!(f, "Repeat for a duration"),
+            Repeat::UntilStopped => write!(f, "Repeat until stopped"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlaybackOptions {
+    pub timing: Timing,
+    /// Only applies to recorded timing, clamped to [`PlaybackOptions::SPEED_RANGE`]
+    pub speed: f64,
+    /// Only applies to fixed delay timing
+    pub fixed_delay: Duration,
+    pub repeat: Repeat,
+    /// Only applies to [`Repeat::Times`], playing zero times plays once
+    pub repeat_count: u32,
+    /// Only applies to [`Repeat::For`], no iteration starts once it is elapsed
+    pub repeat_duration: Duration,
+    pub iteration_delay: Duration,
+}
+
+impl Default for PlaybackOptions {
+    fn default() -> Self {
+        Self {
+            timing: Timing::Recorded,
+            speed: 1.0,
+            fixed_delay: Duration::from_millis(50),
+            repeat: Repeat::Times,
+            repeat_count: 1,
+            repeat_duration: Duration::from_secs(60),
+            iteration_delay: Duration::ZERO,
+        }
+    }
+}
+
+impl PlaybackOptions {
+    pub const SPEED_RANGE: std::ops::RangeInclusive<f64> = 0.25..=10.0;
+
+    /// Number of iterations, if known in advance
+    pub fn total_iterations(&self) -> Option<u32> {
+        match self.repeat {
+            Repeat::Times => Some(self.repeat_count.max(1)),
+            Repeat::For | Repeat::UntilStopped => None,
+        }
+    }
+
+    fn speed(&self) -> f64 {
+        self.speed
+            .clamp(*Self::SPEED_RANGE.start(), *Self::SPEED_RANGE.end())
+    }
+
+    /// How long a recorded `Delay` lasts
+    fn delay(&self, recorded_delay: Duration) -> Duration {
+        match self.timing {
+            Timing::Recorded => recorded_delay.div_f64(self.speed()),
+            Timing::AsFastAsPossible | Timing::FixedDelay => Duration::ZERO,
+        }
+    }
+
+    /// How long to wait after each simulated input
+    fn input_gap(&self) -> Duration {
+        match self.timing {
+            Timing::Recorded => RECORDED_INPUT_GAP.div_f64(self.speed()),
+            Timing::AsFastAsPossible => Duration::ZERO,
+            Timing::FixedDelay => self.fixed_delay,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Command {
-    InitializePlayback(Vec<Event>, Sender<listener::Command>),
+    /// Events, options, and indices of the events to pause before
+    InitializePlayback(
+        Vec<Event>,
+        PlaybackOptions,
+        BTreeSet<usize>,
+        Sender<listener::Command>,
+    ),
     NotifyGrabReady,
+    /// The listener stopped grabbing
+    NotifyGrabReleased,
     StoreMissedEvent(MissedEvent),
     NotifyMissedEventsAddedToGrabber,
+    PausePlayback,
+    ResumePlayback,
+    /// Resumes for a single event, then pauses again
+    StepPlayback,
     StopPlayback,
 }
 
 #[derive(Debug)]
 enum PlayingState {
-    WaitingForGrabMode,
+    /// `held_inputs` are the releases of what a resumed playback held, pressed again
+    /// once grabbing so that the grab expects them
+    WaitingForGrabMode {
+        resuming: bool,
+        held_inputs: Vec<rdev::EventType>,
+    },
     Running,
-    WaitingForMissedEventsAddedToGrabber { yield_end_time: SystemTime },
+    WaitingForMissedEventsAddedToGrabber {
+        yield_end_time: SystemTime,
+    },
+    /// The iteration delay is elapsing, the subscription waits for `starts_at`
+    /// while still handling commands
+    WaitingForNextIteration {
+        starts_at: SystemTime,
+    },
+    /// The events of the iteration starting over, or following a yield without focus change,
+    /// must get through the grab like the first ones
+    WaitingForNextEventsAddedToGrabber,
+    /// The grab is released so the user gets their keyboard back,
+    /// `held_inputs` are the releases simulated once it is, to be pressed again on resume
+    Paused {
+        paused_at: SystemTime,
+        held_inputs: Vec<rdev::EventType>,
+    },
 }
 
 #[derive(Debug)]
@@ -53,19 +220,229 @@ struct Playing {
     event_index: usize,
     listener_command_sender: Sender<listener::Command>,
     events: Vec<Event>,
+    options: PlaybackOptions,
+    iteration: u32,
+    started_at: SystemTime,
     state: PlayingState,
     missed_events: BTreeSet<MissedEvent>,
     yield_context: Option<YieldContext>,
+    /// Window of the last played focus change, mouse moves in a window are relative to it
+    window_title: Option<String>,
+    pressed_inputs: PressedInputs,
+    breakpoints: BTreeSet<usize>,
+    /// Pause before every event, as if each of them had a breakpoint
+    stepping: bool,
+    /// Index of the event the playback paused before, which must play once resumed
+    paused_before: Option<usize>,
+}
+
+/// Keys and buttons pressed through the input backend and not released yet,
+/// kept as the events releasing them, in press order
+#[derive(Debug, Default)]
+struct PressedInputs(Vec<rdev::EventType>);
+
+impl PressedInputs {
+    fn simulate(
+        &mut self,
+        backend: &Backend,
+        event: &rdev::EventType,
+    ) -> Result<(), platform::Error> {
+        backend.input.simulate(event)?;
+        match *event {
+            rdev::EventType::KeyPress(key) => self.press(rdev::EventType::KeyRelease(key)),
+            rdev::EventType::ButtonPress(button) => {
+                self.press(rdev::EventType::ButtonRelease(button))
+            }
+            release @ (rdev::EventType::KeyRelease(_) | rdev::EventType::ButtonRelease(_)) => {
+                self.0.retain(|pressed| *pressed != release)
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn press(&mut self, release: rdev::EventType) {
+        if !self.0.contains(&release) {
+            self.0.push(release);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the releases, in the order their inputs were pressed
+    fn release_all(&mut self, backend: &Backend) -> Vec<rdev::EventType> {
+        for release in self.0.iter().rev() {
+            info!("Player: {release:?} left pressed by the playback");
+            if let Err(e) = backend.input.simulate(release) {
+                error!("Could not simulate {release:?}: {e}");
+            }
+        }
+        std::mem::take(&mut self.0)
+    }
+
+    fn press_again(&mut self, backend: &Backend, releases: Vec<rdev::EventType>) {
+        for press in releases.into_iter().map(press_for) {
+            if let Err(e) = self.simulate(backend, &press) {
+                error!("Could not simulate {press:?}: {e}");
+            }
+        }
+    }
+}
+
+/// What the grab sees when [`Playing::type_char`] types `c`
+fn typed_char_events(backend: &Backend, c: char) -> Vec<rdev::EventType> {
+    match backend.input.typed_char_events(c) {
+        Err(platform::Error::Unsupported(_)) => key_events_for_char(c).unwrap_or_default(),
+        Err(e) => {
+            warn!("Could not tell which events type {c:?}: {e}");
+            Vec::new()
+        }
+        Ok(events) => events,
+    }
+}
+
+/// The press undone by `release`
+fn press_for(release: rdev::EventType) -> rdev::EventType {
+    match release {
+        rdev::EventType::KeyRelease(key) => rdev::EventType::KeyPress(key),
+        rdev::EventType::ButtonRelease(button) => rdev::EventType::ButtonPress(button),
+        event => event,
+    }
 }
 
 impl Playing {
-    pub fn build_simulated_event_for_grab_mode(&self) -> VecDeque<rdev::EventType> {
-        self.events[self.event_index..]
+    /// Gives the keyboard back to the user. What the playback holds is released by
+    /// [`State::notify_grab_released`], once the listener no longer takes the releases
+    /// for user input, and pressed again on resume.
+    fn pause(&mut self, backend: &Backend) {
+        let mut held_inputs = Vec::new();
+        if let Err(e) = self
+            .listener_command_sender
+            .try_send(listener::Command::ChangeMode(listener::Mode::Disabled))
+        {
+            error!("Could not release the grab on pause: {e}");
+            held_inputs = self.pressed_inputs.release_all(backend);
+        }
+        self.state = PlayingState::Paused {
+            paused_at: backend.clock.now(),
+            held_inputs,
+        };
+        info!("Playback paused at index {}", self.event_index);
+    }
+
+    fn should_pause_before_current_event(&mut self) -> bool {
+        if self.paused_before.take() == Some(self.event_index) {
+            return false;
+        }
+        self.stepping || self.breakpoints.contains(&self.event_index)
+    }
+
+    /// Screen coordinates of a move recorded in a window, following the window wherever it is now
+    fn mouse_position(
+        &self,
+        backend: &Backend,
+        screen: (f64, f64),
+        window: (f64, f64),
+    ) -> (f64, f64) {
+        let Some(window_title) = &self.window_title else {
+            warn!(
+                "Mouse move at index {} follows no focus change, using screen coordinates",
+                self.event_index
+            );
+            return screen;
+        };
+        match backend.window.window_origin(window_title) {
+            Some((origin_x, origin_y)) => (origin_x + window.0, origin_y + window.1),
+            None => {
+                warn!("Could not find window \"{window_title}\", using screen coordinates");
+                screen
+            }
+        }
+    }
+
+    /// Types `c` with Unicode injection, or with the keys typing it on a US QWERTY layout
+    /// when the backend cannot inject Unicode
+    fn type_char(&mut self, backend: &Backend, c: char) -> Result<(), platform::Error> {
+        match backend.input.type_char(c) {
+            Err(platform::Error::Unsupported(_)) => {}
+            result => return result,
+        }
+        let Some(key_events) = key_events_for_char(c) else {
+            warn!("No key types {c:?}, skipping it");
+            return Ok(());
+        };
+        for event in key_events {
+            self.pressed_inputs.simulate(backend, &event)?;
+        }
+        Ok(())
+    }
+
+    async fn start_next_iteration(&mut self, backend: &Backend, output: &mut Sender<Message>) {
+        self.event_index = 0;
+        self.iteration += 1;
+        info!("Playback iteration {} started", self.iteration);
+        // The previous iteration used up the inputs the grab lets through
+        let simulated_events = self.build_simulated_event_for_grab_mode(backend, self.event_index);
+        self.state = PlayingState::WaitingForNextEventsAddedToGrabber;
+        self.listener_command_sender
+            .send(listener::Command::SetNextEventsToBeIgnoredByGrab(
+                simulated_events.into(),
+            ))
+            .await
+            .unwrap();
+        output
+            .send(Message::IterationJustStarted {
+                iteration: self.iteration,
+                total: self.options.total_iterations(),
+            })
+            .await
+            .unwrap();
+    }
+
+    fn should_start_over(&self, now: SystemTime) -> bool {
+        if self.events.is_empty() {
+            return false;
+        }
+        match self.options.repeat {
+            Repeat::Times => self.iteration < self.options.repeat_count,
+            Repeat::For => {
+                now.duration_since(self.started_at).unwrap_or_default()
+                    < self.options.repeat_duration
+            }
+            Repeat::UntilStopped => true,
+        }
+    }
+
+    /// Inputs to let through the grab, from the event at `start` up to the next yield.
+    /// Mouse events are left out since the listener never grabs them.
+    pub fn build_simulated_event_for_grab_mode(
+        &self,
+        backend: &Backend,
+        start: usize,
+    ) -> VecDeque<rdev::EventType> {
+        self.events[start..]
             .iter()
             .take_while(|event| !matches!(event.kind, EventKind::YieldFocus))
-            .filter_map(|event| match event.kind {
-                EventKind::Input(Input(event_type)) => Some(event_type),
-                _ => None,
+            .flat_map(|event| match &event.kind {
+                EventKind::Input(Input(event_type)) => vec![*event_type],
+                EventKind::TypeText(text) => text
+                    .chars()
+                    .flat_map(|c| typed_char_events(backend, c))
+                    .collect(),
+                EventKind::Chord { modifiers, key } => Chord {
+                    modifiers: *modifiers,
+                    key: *key,
+                }
+                .key_events(),
+                _ => Vec::new(),
+            })
+            .filter(|event_type| {
+                matches!(
+                    event_type,
+                    rdev::EventType::KeyPress(_) | rdev::EventType::KeyRelease(_)
+                )
             })
             .collect()
     }
@@ -85,7 +462,7 @@ impl Playing {
 
 #[derive(Debug)]
 enum PlayerState {
-    Playing(Playing),
+    Playing(Box<Playing>),
     Idle,
 }
 
@@ -116,32 +493,52 @@ impl PartialEq for MissedEvent {
 impl Eq for MissedEvent {}
 
 #[derive(Debug)]
-struct State {
+pub(super) struct State {
     state: PlayerState,
+    backend: Backend,
+    /// Inputs a stopped playback left pressed, released once the listener stops grabbing
+    held_after_stop: PressedInputs,
 }
 
 impl State {
-    fn new() -> Self {
+    pub(super) fn new(backend: Backend) -> Self {
         Self {
             state: PlayerState::Idle,
+            backend,
+            held_after_stop: Default::default(),
         }
     }
 
-    fn initialize_playback(
+    pub(super) fn initialize_playback(
         &mut self,
         events: Vec<Event>,
+        options: PlaybackOptions,
+        breakpoints: BTreeSet<usize>,
         listener_command_sender: Sender<listener::Command>,
     ) {
+        self.stop_playback();
         let mut playing = Playing {
             event_index: 0,
             listener_command_sender,
             events,
-            state: PlayingState::WaitingForGrabMode,
+            options,
+            iteration: 1,
+            started_at: self.backend.clock.now(),
+            state: PlayingState::WaitingForGrabMode {
+                resuming: false,
+                held_inputs: Vec::new(),
+            },
             missed_events: Default::default(),
             yield_context: None,
+            window_title: None,
+            pressed_inputs: Default::default(),
+            breakpoints,
+            stepping: false,
+            paused_before: None,
         };
 
-        let simulated_events = playing.build_simulated_event_for_grab_mode();
+        let simulated_events =
+            playing.build_simulated_event_for_grab_mode(&self.backend, playing.event_index);
 
         playing
             .listener_command_sender
@@ -150,11 +547,11 @@ impl State {
             }))
             .unwrap();
 
-        self.state = PlayerState::Playing(playing);
+        self.state = PlayerState::Playing(Box::new(playing));
         info!("Player playback initialized: {:#?}", self);
     }
 
-    fn notify_grab_ready(&mut self, mut message_sender: Sender<Message>) {
+    pub(super) fn notify_grab_ready(&mut self, mut message_sender: Sender<Message>) {
         let PlayerState::Playing(playing_state) = &mut self.state else {
             error!(
                 "Invalid player state when receiving NotifyGrabReady command. Expected Playing state, got {:?}",
@@ -162,25 +559,151 @@ impl State {
             );
             return;
         };
-        let PlayingState::WaitingForGrabMode = playing_state.state else {
+        let PlayingState::WaitingForGrabMode {
+            resuming,
+            held_inputs,
+        } = &mut playing_state.state
+        else {
             error!(
                 "Invalid player state when receiving NotifyGrabReady command. Expected WaitingForGrabMode state, got {:?}",
                 playing_state.state
             );
             return;
         };
+        let resuming = *resuming;
+        let held_inputs = std::mem::take(held_inputs);
+        playing_state
+            .pressed_inputs
+            .press_again(&self.backend, held_inputs);
         playing_state.state = PlayingState::Running;
+        let message = if resuming {
+            Message::PlaybackJustResumed
+        } else {
+            playing_state.started_at = self.backend.clock.now();
+            Message::PlaybackJustStarted
+        };
+        message_sender.try_send(message).unwrap();
+    }
+
+    pub(super) fn pause_playback(&mut self, mut message_sender: Sender<Message>) {
+        let PlayerState::Playing(playing_state) = &mut self.state else {
+            error!("Trying to pause while not playing");
+            return;
+        };
+        if !matches!(
+            playing_state.state,
+            PlayingState::Running | PlayingState::WaitingForNextIteration { .. }
+        ) {
+            error!(
+                "Only a running playback can be paused, got {:?}",
+                playing_state.state
+            );
+            return;
+        }
+        playing_state.pause(&self.backend);
         message_sender
-            .try_send(Message::PlaybackJustStarted)
+            .try_send(Message::PlaybackJustPaused)
             .unwrap();
     }
 
-    async fn perform_playback(&mut self, mut output: Sender<Message>) {
+    pub(super) fn resume_playback(&mut self) {
+        self.resume(false);
+    }
+
+    pub(super) fn step_playback(&mut self) {
+        self.resume(true);
+    }
+
+    fn resume(&mut self, stepping: bool) {
         let PlayerState::Playing(playing_state) = &mut self.state else {
+            error!("Trying to resume while not playing");
             return;
         };
+        let PlayingState::Paused {
+            paused_at,
+            held_inputs,
+        } = &mut playing_state.state
+        else {
+            error!(
+                "Only a paused playback can be resumed, got {:?}",
+                playing_state.state
+            );
+            return;
+        };
+        playing_state.stepping = stepping;
+        // Time spent paused does not count towards the repeat duration
+        playing_state.started_at += self
+            .backend
+            .clock
+            .now()
+            .duration_since(*paused_at)
+            .unwrap_or_default();
+        let held_inputs = std::mem::take(held_inputs);
+        // Pressed again once grabbing, so the grab lets them through first
+        let mut simulated_events = held_inputs
+            .iter()
+            .copied()
+            .map(press_for)
+            .filter(|event_type| matches!(event_type, rdev::EventType::KeyPress(_)))
+            .collect::<VecDeque<_>>();
+        simulated_events.extend(
+            playing_state
+                .build_simulated_event_for_grab_mode(&self.backend, playing_state.event_index),
+        );
+        playing_state
+            .listener_command_sender
+            .try_send(listener::Command::ChangeMode(listener::Mode::Grab {
+                simulated_events,
+            }))
+            .unwrap();
+        playing_state.state = PlayingState::WaitingForGrabMode {
+            resuming: true,
+            held_inputs,
+        };
+        info!("Playback resuming at index {}", playing_state.event_index);
+    }
 
-        if !matches!(playing_state.state, PlayingState::Running) {
+    /// When the iteration delay being waited for ends
+    pub(super) fn next_iteration_starts_at(&self) -> Option<SystemTime> {
+        match &self.state {
+            PlayerState::Playing(playing_state) => match playing_state.state {
+                PlayingState::WaitingForNextIteration { starts_at } => Some(starts_at),
+                _ => None,
+            },
+            PlayerState::Idle => None,
+        }
+    }
+
+    pub(super) async fn perform_playback(&mut self, mut output: Sender<Message>) {
+        let PlayerState::Playing(playing_state) = &mut self.state else {
+            return;
+        };
+
+        let now = self.backend.clock.now();
+        match playing_state.state {
+            PlayingState::Running => {}
+            PlayingState::WaitingForNextIteration { starts_at } if now >= starts_at => {
+                playing_state
+                    .start_next_iteration(&self.backend, &mut output)
+                    .await;
+                return;
+            }
+            _ => return,
+        }
+
+        if playing_state.event_index >= playing_state.events.len()
+            && playing_state.should_start_over(now)
+        {
+            let iteration_delay = playing_state.options.iteration_delay;
+            if iteration_delay.is_zero() {
+                playing_state
+                    .start_next_iteration(&self.backend, &mut output)
+                    .await;
+            } else {
+                playing_state.state = PlayingState::WaitingForNextIteration {
+                    starts_at: now + iteration_delay,
+                };
+            }
             return;
         }
 
@@ -191,47 +714,129 @@ impl State {
             return;
         }
 
-        let event = &playing_state.events[playing_state.event_index];
+        if playing_state.should_pause_before_current_event() {
+            playing_state.pause(&self.backend);
+            playing_state.paused_before = Some(playing_state.event_index);
+            output
+                .send(Message::BreakpointHit {
+                    index: playing_state.event_index,
+                })
+                .await
+                .unwrap();
+            return;
+        }
 
-        match &event.kind {
+        let kind = match &playing_state.events[playing_state.event_index].kind {
+            EventKind::MouseMoveInWindow {
+                x,
+                y,
+                window_x,
+                window_y,
+            } => {
+                let (x, y) =
+                    playing_state.mouse_position(&self.backend, (*x, *y), (*window_x, *window_y));
+                EventKind::Input(Input(rdev::EventType::MouseMove { x, y }))
+            }
+            kind => kind.clone(),
+        };
+
+        match &kind {
             EventKind::Input(Input(event)) => {
-                rdev::simulate(event).unwrap();
-                Timer::after(Duration::from_millis(16)).await;
+                let event = *event;
+                if let Err(e) = playing_state.pressed_inputs.simulate(&self.backend, &event) {
+                    error!("Could not simulate {event:?}, stopping playback: {e}");
+                    self.stop_playback();
+                    output.send(Message::PlaybackDone).await.unwrap();
+                    return;
+                }
+                let input_gap = playing_state.options.input_gap();
+                if !input_gap.is_zero() {
+                    self.backend.clock.sleep(input_gap).await;
+                }
+            }
+            EventKind::MouseMoveInWindow { .. } => unreachable!("played as a mouse move input"),
+            EventKind::TypeText(text) => {
+                let input_gap = playing_state.options.input_gap();
+                for c in text.chars() {
+                    if let Err(e) = playing_state.type_char(&self.backend, c) {
+                        error!("Could not type {c:?}, stopping playback: {e}");
+                        self.stop_playback();
+                        output.send(Message::PlaybackDone).await.unwrap();
+                        return;
+                    }
+                    if !input_gap.is_zero() {
+                        self.backend.clock.sleep(input_gap).await;
+                    }
+                }
+            }
+            EventKind::Chord { modifiers, key } => {
+                let input_gap = playing_state.options.input_gap();
+                let chord = Chord {
+                    modifiers: *modifiers,
+                    key: *key,
+                };
+                for event in chord.key_events() {
+                    if let Err(e) = playing_state.pressed_inputs.simulate(&self.backend, &event) {
+                        error!("Could not simulate {event:?}, stopping playback: {e}");
+                        self.stop_playback();
+                        output.send(Message::PlaybackDone).await.unwrap();
+                        return;
+                    }
+                    if !input_gap.is_zero() {
+                        self.backend.clock.sleep(input_gap).await;
+                    }
+                }
             }
             EventKind::FocusChange { window_title } => {
-                if let Ok(window_title) = get_focused_window_title() {
+                playing_state.window_title = Some(window_title.clone());
+                if let Some(previous_window_title) = self.backend.window.focused_window_title() {
                     playing_state.yield_context = Some(YieldContext {
-                        previous_window_title: window_title,
-                        start_time: SystemTime::now(),
+                        previous_window_title,
+                        start_time: self.backend.clock.now(),
                     });
                 }
-                set_focused_window_by_title(window_title);
+                if let Err(e) = self.backend.window.focus_window_by_title(window_title) {
+                    warn!("Could not focus window \"{window_title}\": {e}");
+                }
             }
             EventKind::Delay(duration) => {
-                Timer::after(*duration).await;
+                let delay = playing_state.options.delay(*duration);
+                if !delay.is_zero() {
+                    self.backend.clock.sleep(delay).await;
+                }
             }
             EventKind::YieldFocus => {
-                if let Some(yield_context) = &playing_state.yield_context {
-                    let end_time = SystemTime::now();
+                // The grab used up the events before the yield, the next ones follow
+                // the replayed missed events
+                let next_events = playing_state.build_simulated_event_for_grab_mode(
+                    &self.backend,
+                    playing_state.event_index + 1,
+                );
+                let events_to_be_ignored = if let Some(yield_context) = &playing_state.yield_context
+                {
+                    let end_time = self.backend.clock.now();
                     playing_state.state = PlayingState::WaitingForMissedEventsAddedToGrabber {
                         yield_end_time: end_time,
                     };
-                    let yield_time_missed_events = playing_state
-                        .filtered_missed_events(yield_context.start_time, end_time)
-                        .collect_vec();
                     playing_state
-                        .listener_command_sender
-                        .send(listener::Command::SetNextEventsToBeIgnoredByGrab(
-                            yield_time_missed_events,
-                        ))
-                        .await
-                        .unwrap();
+                        .filtered_missed_events(yield_context.start_time, end_time)
+                        .chain(next_events)
+                        .collect_vec()
                 } else {
                     warn!(
                         "No yield context for yield focus at index {}: Make sure to focus a window before yielding context",
                         playing_state.event_index
                     );
-                }
+                    playing_state.state = PlayingState::WaitingForNextEventsAddedToGrabber;
+                    next_events.into()
+                };
+                playing_state
+                    .listener_command_sender
+                    .send(listener::Command::SetNextEventsToBeIgnoredByGrab(
+                        events_to_be_ignored,
+                    ))
+                    .await
+                    .unwrap();
             }
         }
 
@@ -245,29 +850,67 @@ impl State {
             .unwrap();
     }
 
-    fn stop_playback(&mut self) {
+    /// Goes back to idle. What the playback left pressed is released by
+    /// [`Self::notify_grab_released`], once the listener no longer takes the releases
+    /// for user input.
+    pub(super) fn stop_playback(&mut self) {
+        if let PlayerState::Playing(playing_state) = &mut self.state
+            && !playing_state.pressed_inputs.is_empty()
+        {
+            for release in std::mem::take(&mut playing_state.pressed_inputs.0) {
+                self.held_after_stop.press(release);
+            }
+            if let Err(e) = playing_state
+                .listener_command_sender
+                .try_send(listener::Command::ChangeMode(listener::Mode::Disabled))
+            {
+                error!("Could not disable listener before releasing inputs: {e}");
+                self.held_after_stop.release_all(&self.backend);
+            }
+        }
         self.state = PlayerState::Idle;
     }
 
-    fn store_missed_event(&mut self, event: MissedEvent) {
-        let PlayerState::Playing(Playing {
-            state: PlayingState::Running,
-            missed_events,
-            ..
-        }) = &mut self.state
-        else {
+    /// Releases what a stopped playback left pressed, so nothing gets stuck,
+    /// or what a paused playback holds, to be pressed again on resume
+    pub(super) fn notify_grab_released(&mut self) {
+        if !self.held_after_stop.is_empty() {
+            self.held_after_stop.release_all(&self.backend);
+        }
+        if let PlayerState::Playing(playing_state) = &mut self.state
+            && let PlayingState::Paused { held_inputs, .. } = &mut playing_state.state
+            && !playing_state.pressed_inputs.is_empty()
+        {
+            held_inputs.extend(playing_state.pressed_inputs.release_all(&self.backend));
+        }
+    }
+
+    pub(super) fn store_missed_event(&mut self, event: MissedEvent) {
+        let PlayerState::Playing(playing_state) = &mut self.state else {
             error!("Expected running player while storing missed event");
             return;
         };
-        missed_events.insert(event);
+        let PlayingState::Running = playing_state.state else {
+            error!("Expected running player while storing missed event");
+            return;
+        };
+        playing_state.missed_events.insert(event);
     }
 
-    async fn notify_missed_events_added_to_grabber(&mut self) {
+    pub(super) async fn notify_missed_events_added_to_grabber(
+        &mut self,
+        mut output: Sender<Message>,
+    ) {
         let PlayerState::Playing(playing_state) = &mut self.state else {
             error!("notify_missed_events_added_to_grabber should not be called if not playing");
             return;
         };
 
+        if let PlayingState::WaitingForNextEventsAddedToGrabber = playing_state.state {
+            playing_state.state = PlayingState::Running;
+            return;
+        }
+
         let PlayingState::WaitingForMissedEventsAddedToGrabber { yield_end_time } =
             playing_state.state
         else {
@@ -284,13 +927,31 @@ impl State {
             return;
         };
 
-        set_focused_window_by_title(yield_context.previous_window_title);
+        if let Err(e) = self
+            .backend
+            .window
+            .focus_window_by_title(&yield_context.previous_window_title)
+        {
+            warn!(
+                "Could not restore window \"{}\": {e}",
+                yield_context.previous_window_title
+            );
+        }
 
-        for missed_event in
-            playing_state.filtered_missed_events(yield_context.start_time, yield_end_time)
+        for missed_event in playing_state
+            .filtered_missed_events(yield_context.start_time, yield_end_time)
+            .collect_vec()
         {
-            rdev::simulate(&missed_event).unwrap();
-            Timer::after(Duration::from_millis(20)).await;
+            if let Err(e) = playing_state
+                .pressed_inputs
+                .simulate(&self.backend, &missed_event)
+            {
+                error!("Could not replay {missed_event:?}, stopping playback: {e}");
+                self.stop_playback();
+                output.send(Message::PlaybackDone).await.unwrap();
+                return;
+            }
+            self.backend.clock.sleep(Duration::from_millis(20)).await;
         }
 
         playing_state.missed_events = playing_state
@@ -307,35 +968,47 @@ impl State {
 
 pub fn subscription() -> impl Stream<Item = Message> {
     stream::channel(100, async |mut output| {
-        let mut player = State::new();
+        let mut player = State::new(Backend::native());
         let (command_tx, mut command_rx) = channel(100);
         output.send(Message::SenderReady(command_tx)).await.unwrap();
 
         loop {
-            let command = if matches!(
-                player.state,
-                PlayerState::Playing(Playing {
-                    state: PlayingState::Running,
-                    ..
-                })
-            ) {
+            let command = if let PlayerState::Playing(playing_state) = &player.state
+                && let PlayingState::Running = playing_state.state
+            {
                 command_rx.try_next()
+            } else if let Some(starts_at) = player.next_iteration_starts_at() {
+                let iteration_delay = starts_at
+                    .duration_since(player.backend.clock.now())
+                    .unwrap_or_default();
+                let iteration_delay_elapsed = player.backend.clock.sleep(iteration_delay);
+                Ok(smol::future::or(command_rx.next(), async {
+                    iteration_delay_elapsed.await;
+                    None
+                })
+                .await)
             } else {
                 Ok(command_rx.next().await)
             };
             if let Ok(Some(command)) = command {
                 trace!("Player command: {command:#?}");
                 match command {
-                    Command::InitializePlayback(events, sender) => {
-                        player.initialize_playback(events, sender)
+                    Command::InitializePlayback(events, options, breakpoints, sender) => {
+                        player.initialize_playback(events, options, breakpoints, sender)
                     }
                     Command::NotifyGrabReady => player.notify_grab_ready(output.clone()),
+                    Command::NotifyGrabReleased => player.notify_grab_released(),
                     Command::StoreMissedEvent(missed_event) => {
                         player.store_missed_event(missed_event)
                     }
                     Command::NotifyMissedEventsAddedToGrabber => {
-                        player.notify_missed_events_added_to_grabber().await;
+                        player
+                            .notify_missed_events_added_to_grabber(output.clone())
+                            .await;
                     }
+                    Command::PausePlayback => player.pause_playback(output.clone()),
+                    Command::ResumePlayback => player.resume_playback(),
+                    Command::StepPlayback => player.step_playback(),
                     Command::StopPlayback => {
                         player.stop_playback();
                         output.send(Message::PlaybackDone).await.unwrap();
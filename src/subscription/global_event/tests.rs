@@ -0,0 +1,1472 @@
+use std::{
+    collections::BTreeSet,
+    time::{Duration, SystemTime},
+};
+
+use iced::futures::channel::mpsc::{Receiver, Sender, channel};
+use rdev::{EventType, Key};
+
+use crate::{
+    platform::mock::{Action, Mock},
+    subscription::global_event::{Event, EventKind, Input, chord, listener, player},
+};
+
+#[derive(Debug, PartialEq)]
+enum Observed {
+    Started,
+    Paused,
+    Resumed,
+    Played(usize),
+    Iteration(u32),
+    Breakpoint(usize),
+    Done,
+}
+
+/// Wires the listener and the player together the way the main window does,
+/// on top of the mock backend and its virtual clock
+struct Harness {
+    mock: Mock,
+    player: player::State,
+    listener: listener::State,
+    listener_command_tx: Sender<listener::Command>,
+    listener_command_rx: Receiver<listener::Command>,
+    listener_message_tx: Sender<listener::Message>,
+    listener_message_rx: Receiver<listener::Message>,
+    player_message_tx: Sender<player::Message>,
+    player_message_rx: Receiver<player::Message>,
+    observed: Vec<Observed>,
+    /// Number of journal entries already fed back by [`Harness::echo_simulated`]
+    echoed: usize,
+}
+
+impl Harness {
+    fn new(focused_window_title: &str) -> Self {
+        let mock = Mock::new(focused_window_title);
+        let (listener_command_tx, listener_command_rx) = channel(100);
+        let (listener_message_tx, listener_message_rx) = channel(100);
+        let (player_message_tx, player_message_rx) = channel(100);
+        Self {
+            player: player::State::new(mock.backend.clone()),
+            listener: listener::State::new(mock.backend.clone()),
+            mock,
+            listener_command_tx,
+            listener_command_rx,
+            listener_message_tx,
+            listener_message_rx,
+            player_message_tx,
+            player_message_rx,
+            observed: Vec::new(),
+            echoed: 0,
+        }
+    }
+
+    async fn start(&mut self, events: Vec<Event>) {
+        self.start_with(events, Default::default()).await;
+    }
+
+    async fn start_with(&mut self, events: Vec<Event>, options: player::PlaybackOptions) {
+        self.player.initialize_playback(
+            events,
+            options,
+            Default::default(),
+            self.listener_command_tx.clone(),
+        );
+        self.pump().await;
+    }
+
+    async fn start_with_breakpoints(&mut self, events: Vec<Event>, breakpoints: BTreeSet<usize>) {
+        self.player.initialize_playback(
+            events,
+            Default::default(),
+            breakpoints,
+            self.listener_command_tx.clone(),
+        );
+        self.pump().await;
+    }
+
+    /// Delivers every pending command and message until the system settles
+    async fn pump(&mut self) {
+        loop {
+            let mut settled = true;
+
+            while let Ok(Some(command)) = self.listener_command_rx.try_next() {
+                settled = false;
+                self.listener
+                    .handle_command(command, self.listener_message_tx.clone())
+                    .await;
+            }
+
+            while let Ok(Some(message)) = self.listener_message_rx.try_next() {
+                settled = false;
+                match message {
+                    listener::Message::ModeJustSet(listener::Mode::Grab { .. }) => self
+                        .player
+                        .notify_grab_ready(self.player_message_tx.clone()),
+                    listener::Message::ModeJustSet(listener::Mode::Disabled) => {
+                        self.player.notify_grab_released()
+                    }
+                    listener::Message::SetNextEventsToBeIgnoredByGrabDone => {
+                        self.player
+                            .notify_missed_events_added_to_grabber(self.player_message_tx.clone())
+                            .await
+                    }
+                    listener::Message::Event(Event {
+                        kind: EventKind::Input(Input(event)),
+                        time,
+                    }) => self
+                        .player
+                        .store_missed_event(player::MissedEvent { event, time }),
+                    // The main window stops the player, which reports it is done
+                    listener::Message::PanicHotkeyPressed => {
+                        self.player.stop_playback();
+                        self.observed.push(Observed::Done);
+                    }
+                    _ => {}
+                }
+            }
+
+            while let Ok(Some(message)) = self.player_message_rx.try_next() {
+                settled = false;
+                match message {
+                    player::Message::PlaybackJustStarted => self.observed.push(Observed::Started),
+                    player::Message::PlaybackJustPaused => self.observed.push(Observed::Paused),
+                    player::Message::PlaybackJustResumed => self.observed.push(Observed::Resumed),
+                    player::Message::JustPlayed { index } => {
+                        self.observed.push(Observed::Played(index))
+                    }
+                    player::Message::IterationJustStarted { iteration, .. } => {
+                        self.observed.push(Observed::Iteration(iteration))
+                    }
+                    player::Message::BreakpointHit { index } => {
+                        self.observed.push(Observed::Breakpoint(index))
+                    }
+                    player::Message::PlaybackDone => self.observed.push(Observed::Done),
+                    player::Message::SenderReady(_) => {}
+                }
+            }
+
+            if settled {
+                return;
+            }
+        }
+    }
+
+    /// Waits for the iteration delay the way the subscription does, then plays the next event
+    async fn perform_playback(&mut self) {
+        if let Some(starts_at) = self.player.next_iteration_starts_at() {
+            let now = self.mock.backend.clock.now();
+            self.mock
+                .clock
+                .advance(starts_at.duration_since(now).unwrap_or_default());
+        }
+        self.player
+            .perform_playback(self.player_message_tx.clone())
+            .await;
+    }
+
+    async fn step(&mut self) {
+        self.perform_playback().await;
+        self.pump().await;
+    }
+
+    async fn run_to_end(&mut self) {
+        self.run_until(Observed::Done).await;
+    }
+
+    async fn run_until(&mut self, expected: Observed) {
+        for _ in 0..1000 {
+            if self.observed.last() == Some(&expected) {
+                return;
+            }
+            self.step().await;
+        }
+        panic!("Playback never reached {expected:?}: {:?}", self.observed);
+    }
+
+    /// Physical key stroke, returns whether the listener let each event reach the system
+    async fn user_types(&mut self, key: Key) -> (bool, bool) {
+        let mut forwarded = [false; 2];
+        for (index, event_type) in [EventType::KeyPress(key), EventType::KeyRelease(key)]
+            .into_iter()
+            .enumerate()
+        {
+            let event = rdev::Event {
+                time: self.mock.backend.clock.now(),
+                name: None,
+                event_type,
+            };
+            forwarded[index] = self
+                .listener
+                .on_key_event(event, self.listener_message_tx.clone())
+                .await
+                .is_some();
+            self.mock.clock.advance(Duration::from_millis(10));
+            self.pump().await;
+        }
+        (forwarded[0], forwarded[1])
+    }
+
+    /// Feeds the inputs simulated since the last call back through the listener, the way
+    /// the system does, and returns them along with whether the listener let them through.
+    /// They all reach the listener before the commands sent along with them.
+    async fn echo_simulated(&mut self) -> Vec<(EventType, bool)> {
+        let journal = self.mock.journal();
+        let simulated = journal[self.echoed..]
+            .iter()
+            .flat_map(|action| match action {
+                Action::Simulate(event_type) => vec![*event_type],
+                Action::TypeChar(c) => self.mock.backend.input.typed_char_events(*c).unwrap(),
+                Action::Focus(_) => Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        self.echoed = journal.len();
+        let mut echoed = Vec::new();
+        for event_type in simulated {
+            let event = rdev::Event {
+                time: self.mock.backend.clock.now(),
+                name: None,
+                event_type,
+            };
+            let forwarded = self
+                .listener
+                .on_key_event(event, self.listener_message_tx.clone())
+                .await
+                .is_some();
+            echoed.push((event_type, forwarded));
+        }
+        self.pump().await;
+        echoed
+    }
+
+    /// Plays the next event, echoing what it simulated
+    async fn step_echoing(&mut self) -> Vec<(EventType, bool)> {
+        self.perform_playback().await;
+        self.echo_simulated().await
+    }
+
+    async fn run_to_end_echoing(&mut self) -> Vec<(EventType, bool)> {
+        self.run_until_echoing(Observed::Done).await
+    }
+
+    async fn run_until_echoing(&mut self, expected: Observed) -> Vec<(EventType, bool)> {
+        let mut echoed = Vec::new();
+        for _ in 0..1000 {
+            if self.observed.last() == Some(&expected) {
+                return echoed;
+            }
+            echoed.extend(self.step_echoing().await);
+        }
+        panic!("Playback never reached {expected:?}: {:?}", self.observed);
+    }
+}
+
+fn event(kind: EventKind) -> Event {
+    Event::new(SystemTime::UNIX_EPOCH, kind)
+}
+
+fn press(key: Key) -> Event {
+    event(EventKind::Input(Input(EventType::KeyPress(key))))
+}
+
+fn release(key: Key) -> Event {
+    event(EventKind::Input(Input(EventType::KeyRelease(key))))
+}
+
+fn focus(window_title: &str) -> Event {
+    event(EventKind::FocusChange {
+        window_title: window_title.into(),
+    })
+}
+
+fn simulated(event_type: EventType) -> Action {
+    Action::Simulate(event_type)
+}
+
+#[test]
+fn plays_events_in_order_and_reports_progress() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness
+            .start(vec![
+                press(Key::KeyA),
+                event(EventKind::Delay(Duration::from_millis(100))),
+                release(Key::KeyA),
+            ])
+            .await;
+        harness.run_to_end().await;
+
+        assert_eq!(
+            harness.mock.journal(),
+            [
+                simulated(EventType::KeyPress(Key::KeyA)),
+                simulated(EventType::KeyRelease(Key::KeyA)),
+            ]
+        );
+        assert_eq!(
+            harness.observed,
+            [
+                Observed::Started,
+                Observed::Played(0),
+                Observed::Played(1),
+                Observed::Played(2),
+                Observed::Done,
+            ]
+        );
+        assert_eq!(
+            harness.mock.backend.clock.now(),
+            SystemTime::UNIX_EPOCH + Duration::from_millis(132)
+        );
+    });
+}
+
+#[test]
+fn replays_input_typed_while_playback_yields_focus() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness
+            .start(vec![
+                focus("Target"),
+                press(Key::KeyA),
+                release(Key::KeyA),
+                event(EventKind::YieldFocus),
+                press(Key::KeyB),
+                release(Key::KeyB),
+            ])
+            .await;
+        harness.step().await;
+
+        assert_eq!(harness.user_types(Key::KeyX).await, (false, false));
+
+        harness.run_to_end().await;
+
+        assert_eq!(
+            harness.mock.journal(),
+            [
+                Action::Focus("Target".into()),
+                simulated(EventType::KeyPress(Key::KeyA)),
+                simulated(EventType::KeyRelease(Key::KeyA)),
+                Action::Focus("Editor".into()),
+                simulated(EventType::KeyPress(Key::KeyX)),
+                simulated(EventType::KeyRelease(Key::KeyX)),
+                simulated(EventType::KeyPress(Key::KeyB)),
+                simulated(EventType::KeyRelease(Key::KeyB)),
+            ]
+        );
+        assert_eq!(
+            harness.mock.focused_window_title().as_deref(),
+            Some("Editor")
+        );
+        assert_eq!(
+            harness.observed,
+            [
+                Observed::Started,
+                Observed::Played(0),
+                Observed::Played(1),
+                Observed::Played(2),
+                Observed::Played(3),
+                Observed::Played(4),
+                Observed::Played(5),
+                Observed::Done,
+            ]
+        );
+    });
+}
+
+#[test]
+fn lets_the_inputs_after_a_yield_through_the_grab() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness
+            .start(vec![
+                focus("Target"),
+                press(Key::KeyA),
+                release(Key::KeyA),
+                event(EventKind::YieldFocus),
+                press(Key::KeyB),
+                release(Key::KeyB),
+            ])
+            .await;
+        harness.step_echoing().await;
+        assert_eq!(harness.user_types(Key::KeyX).await, (false, false));
+
+        assert_eq!(
+            harness.run_to_end_echoing().await,
+            [
+                (EventType::KeyPress(Key::KeyA), true),
+                (EventType::KeyRelease(Key::KeyA), true),
+                (EventType::KeyPress(Key::KeyX), true),
+                (EventType::KeyRelease(Key::KeyX), true),
+                (EventType::KeyPress(Key::KeyB), true),
+                (EventType::KeyRelease(Key::KeyB), true),
+            ]
+        );
+    });
+}
+
+#[test]
+fn keeps_input_typed_after_a_yield_for_the_next_one() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness
+            .start(vec![
+                focus("Target"),
+                press(Key::KeyA),
+                event(EventKind::YieldFocus),
+                focus("Target"),
+                release(Key::KeyA),
+                event(EventKind::YieldFocus),
+            ])
+            .await;
+        harness.step().await;
+        harness.user_types(Key::KeyX).await;
+        harness.step().await;
+        harness.step().await;
+        harness.step().await;
+        harness.user_types(Key::KeyY).await;
+        harness.run_to_end().await;
+
+        assert_eq!(
+            harness.mock.journal(),
+            [
+                Action::Focus("Target".into()),
+                simulated(EventType::KeyPress(Key::KeyA)),
+                Action::Focus("Editor".into()),
+                simulated(EventType::KeyPress(Key::KeyX)),
+                simulated(EventType::KeyRelease(Key::KeyX)),
+                Action::Focus("Target".into()),
+                simulated(EventType::KeyRelease(Key::KeyA)),
+                Action::Focus("Editor".into()),
+                simulated(EventType::KeyPress(Key::KeyY)),
+                simulated(EventType::KeyRelease(Key::KeyY)),
+            ]
+        );
+    });
+}
+
+#[test]
+fn grabs_user_input_when_simulated_input_bypasses_the_grab() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness.mock.simulate_around_grab();
+        harness
+            .start(vec![press(Key::KeyA), release(Key::KeyA)])
+            .await;
+
+        // What the playback simulates never reaches the listener, matching it means nothing
+        assert_eq!(harness.user_types(Key::KeyA).await, (false, false));
+    });
+}
+
+#[test]
+fn playback_waits_for_grab_mode() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness.player.initialize_playback(
+            vec![press(Key::KeyA)],
+            Default::default(),
+            Default::default(),
+            harness.listener_command_tx.clone(),
+        );
+        harness
+            .player
+            .perform_playback(harness.player_message_tx.clone())
+            .await;
+
+        assert!(harness.mock.journal().is_empty());
+
+        harness.pump().await;
+        harness.run_to_end().await;
+
+        assert_eq!(
+            harness.mock.journal(),
+            [
+                simulated(EventType::KeyPress(Key::KeyA)),
+                simulated(EventType::KeyRelease(Key::KeyA)),
+            ]
+        );
+    });
+}
+
+#[test]
+fn swallows_hotkeys_while_disabled() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        let hotkey = "Ctrl+F5".parse().unwrap();
+        harness
+            .listener
+            .handle_command(
+                listener::Command::SetHotkeys([hotkey].into()),
+                harness.listener_message_tx.clone(),
+            )
+            .await;
+
+        let mut forwarded = Vec::new();
+        for event_type in [
+            EventType::KeyPress(Key::ControlLeft),
+            EventType::KeyPress(Key::F5),
+            EventType::KeyRelease(Key::F5),
+            EventType::KeyRelease(Key::ControlLeft),
+            EventType::KeyPress(Key::F5),
+        ] {
+            let event = rdev::Event {
+                time: harness.mock.backend.clock.now(),
+                name: None,
+                event_type,
+            };
+            forwarded.push(
+                harness
+                    .listener
+                    .on_key_event(event, harness.listener_message_tx.clone())
+                    .await
+                    .is_some(),
+            );
+        }
+
+        assert_eq!(forwarded, [true, false, false, true, true]);
+        assert!(matches!(
+            harness.listener_message_rx.try_next(),
+            Ok(Some(listener::Message::HotkeyPressed(chord))) if chord == hotkey
+        ));
+        assert!(harness.listener_message_rx.try_next().is_err());
+    });
+}
+
+#[test]
+fn triggers_a_held_hotkey_once() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        let hotkey = "Ctrl+F5".parse().unwrap();
+        harness
+            .listener
+            .handle_command(
+                listener::Command::SetHotkeys([hotkey].into()),
+                harness.listener_message_tx.clone(),
+            )
+            .await;
+
+        let mut forwarded = Vec::new();
+        // Backends report auto-repeat as more presses of the held key
+        for event_type in [
+            EventType::KeyPress(Key::ControlLeft),
+            EventType::KeyPress(Key::F5),
+            EventType::KeyPress(Key::F5),
+            EventType::KeyPress(Key::F5),
+            EventType::KeyRelease(Key::F5),
+            EventType::KeyRelease(Key::ControlLeft),
+        ] {
+            let event = rdev::Event {
+                time: harness.mock.backend.clock.now(),
+                name: None,
+                event_type,
+            };
+            forwarded.push(
+                harness
+                    .listener
+                    .on_key_event(event, harness.listener_message_tx.clone())
+                    .await
+                    .is_some(),
+            );
+        }
+
+        assert_eq!(forwarded, [true, false, false, false, false, true]);
+        assert!(matches!(
+            harness.listener_message_rx.try_next(),
+            Ok(Some(listener::Message::HotkeyPressed(chord))) if chord == hotkey
+        ));
+        assert!(harness.listener_message_rx.try_next().is_err());
+    });
+}
+
+#[test]
+fn captures_the_next_chord_then_disables_itself() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness
+            .listener
+            .handle_command(
+                listener::Command::ChangeMode(listener::Mode::CaptureKey),
+                harness.listener_message_tx.clone(),
+            )
+            .await;
+
+        let mut forwarded = Vec::new();
+        for event_type in [
+            EventType::KeyPress(Key::ShiftLeft),
+            EventType::KeyPress(Key::KeyK),
+            EventType::KeyRelease(Key::KeyK),
+            EventType::KeyRelease(Key::ShiftLeft),
+            EventType::KeyPress(Key::KeyK),
+        ] {
+            let event = rdev::Event {
+                time: harness.mock.backend.clock.now(),
+                name: None,
+                event_type,
+            };
+            forwarded.push(
+                harness
+                    .listener
+                    .on_key_event(event, harness.listener_message_tx.clone())
+                    .await
+                    .is_some(),
+            );
+        }
+
+        assert_eq!(forwarded, [true, false, false, true, true]);
+        let mut captured = Vec::new();
+        while let Ok(Some(message)) = harness.listener_message_rx.try_next() {
+            if let listener::Message::KeyCaptured(chord) = message {
+                captured.push(chord);
+            }
+        }
+        assert_eq!(captured, ["Shift+KeyK".parse().unwrap()]);
+    });
+}
+
+#[test]
+fn panic_hotkey_stops_playback_and_releases_held_keys() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness
+            .start(vec![
+                press(Key::ShiftLeft),
+                press(Key::KeyA),
+                release(Key::KeyA),
+                press(Key::KeyB),
+                release(Key::KeyB),
+                release(Key::ShiftLeft),
+            ])
+            .await;
+        harness.step().await;
+        harness.step().await;
+
+        let mut forwarded = Vec::new();
+        for event_type in [
+            EventType::KeyPress(Key::ControlLeft),
+            EventType::KeyPress(Key::Alt),
+            EventType::KeyPress(Key::Escape),
+            EventType::KeyRelease(Key::Escape),
+        ] {
+            let event = rdev::Event {
+                time: harness.mock.backend.clock.now(),
+                name: None,
+                event_type,
+            };
+            forwarded.push(
+                harness
+                    .listener
+                    .on_key_event(event, harness.listener_message_tx.clone())
+                    .await
+                    .is_some(),
+            );
+            harness.pump().await;
+        }
+        harness.step().await;
+
+        assert_eq!(forwarded, [false, false, false, false]);
+        assert_eq!(
+            harness.mock.journal(),
+            [
+                simulated(EventType::KeyPress(Key::ShiftLeft)),
+                simulated(EventType::KeyPress(Key::KeyA)),
+                simulated(EventType::KeyRelease(Key::KeyA)),
+                simulated(EventType::KeyRelease(Key::ShiftLeft)),
+            ]
+        );
+        assert_eq!(
+            harness.observed,
+            [
+                Observed::Started,
+                Observed::Played(0),
+                Observed::Played(1),
+                Observed::Done,
+            ]
+        );
+    });
+}
+
+#[test]
+fn releases_inputs_left_pressed_when_playback_ends() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness
+            .start(vec![
+                focus("Target"),
+                press(Key::ControlLeft),
+                event(EventKind::Input(Input(EventType::ButtonPress(
+                    rdev::Button::Left,
+                )))),
+                event(EventKind::YieldFocus),
+            ])
+            .await;
+        harness.step().await;
+
+        // Only the press gets replayed by the yield, the release comes after it
+        let event = rdev::Event {
+            time: harness.mock.backend.clock.now(),
+            name: None,
+            event_type: EventType::KeyPress(Key::KeyX),
+        };
+        harness
+            .listener
+            .on_key_event(event, harness.listener_message_tx.clone())
+            .await;
+        harness.pump().await;
+        harness.run_to_end().await;
+
+        assert_eq!(
+            harness.mock.journal(),
+            [
+                Action::Focus("Target".into()),
+                simulated(EventType::KeyPress(Key::ControlLeft)),
+                simulated(EventType::ButtonPress(rdev::Button::Left)),
+                Action::Focus("Editor".into()),
+                simulated(EventType::KeyPress(Key::KeyX)),
+                simulated(EventType::KeyRelease(Key::KeyX)),
+                simulated(EventType::ButtonRelease(rdev::Button::Left)),
+                simulated(EventType::KeyRelease(Key::ControlLeft)),
+            ]
+        );
+    });
+}
+
+#[test]
+fn releases_held_keys_once_the_grab_is_released() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness
+            .start(vec![
+                press(Key::ShiftLeft),
+                press(Key::KeyA),
+                release(Key::KeyA),
+                release(Key::ShiftLeft),
+            ])
+            .await;
+        harness.step().await;
+        assert_eq!(
+            harness.echo_simulated().await,
+            [(EventType::KeyPress(Key::ShiftLeft), true)]
+        );
+
+        harness.player.stop_playback();
+        // Still grabbing, the release would be taken for user input
+        assert_eq!(
+            harness.mock.journal(),
+            [simulated(EventType::KeyPress(Key::ShiftLeft))]
+        );
+
+        harness.pump().await;
+        assert_eq!(
+            harness.echo_simulated().await,
+            [(EventType::KeyRelease(Key::ShiftLeft), true)]
+        );
+    });
+}
+
+#[test]
+fn stops_and_releases_inputs_when_the_backend_fails() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness.mock.fail_on(EventType::KeyPress(Key::KeyA));
+        harness
+            .start(vec![
+                press(Key::ShiftLeft),
+                press(Key::KeyA),
+                release(Key::KeyA),
+                release(Key::ShiftLeft),
+            ])
+            .await;
+        harness.run_to_end().await;
+
+        assert_eq!(
+            harness.mock.journal(),
+            [
+                simulated(EventType::KeyPress(Key::ShiftLeft)),
+                simulated(EventType::KeyRelease(Key::ShiftLeft)),
+            ]
+        );
+        assert_eq!(
+            harness.observed,
+            [Observed::Started, Observed::Played(0), Observed::Done]
+        );
+    });
+}
+
+#[test]
+fn scales_or_replaces_recorded_timing() {
+    let events = || {
+        vec![
+            press(Key::KeyA),
+            event(EventKind::Delay(Duration::from_millis(100))),
+            release(Key::KeyA),
+        ]
+    };
+    let elapsed = |options| {
+        smol::block_on(async {
+            let mut harness = Harness::new("Editor");
+            harness.start_with(events(), options).await;
+            harness.run_to_end().await;
+            harness
+                .mock
+                .backend
+                .clock
+                .now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+        })
+    };
+
+    assert_eq!(
+        elapsed(player::PlaybackOptions {
+            speed: 4.0,
+            ..Default::default()
+        }),
+        Duration::from_millis(33)
+    );
+    assert_eq!(
+        elapsed(player::PlaybackOptions {
+            speed: 100.0,
+            ..Default::default()
+        }),
+        Duration::from_micros(13_200)
+    );
+    assert_eq!(
+        elapsed(player::PlaybackOptions {
+            timing: player::Timing::AsFastAsPossible,
+            ..Default::default()
+        }),
+        Duration::ZERO
+    );
+    assert_eq!(
+        elapsed(player::PlaybackOptions {
+            timing: player::Timing::FixedDelay,
+            fixed_delay: Duration::from_millis(5),
+            ..Default::default()
+        }),
+        Duration::from_millis(10)
+    );
+}
+
+#[test]
+fn repeats_a_number_of_times_with_a_delay_between_iterations() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness
+            .start_with(
+                vec![press(Key::KeyA), release(Key::KeyA)],
+                player::PlaybackOptions {
+                    timing: player::Timing::AsFastAsPossible,
+                    repeat: player::Repeat::Times,
+                    repeat_count: 3,
+                    iteration_delay: Duration::from_millis(100),
+                    ..Default::default()
+                },
+            )
+            .await;
+        harness.run_to_end().await;
+
+        assert_eq!(harness.mock.journal().len(), 6);
+        assert_eq!(
+            harness.observed,
+            [
+                Observed::Started,
+                Observed::Played(0),
+                Observed::Played(1),
+                Observed::Iteration(2),
+                Observed::Played(0),
+                Observed::Played(1),
+                Observed::Iteration(3),
+                Observed::Played(0),
+                Observed::Played(1),
+                Observed::Done,
+            ]
+        );
+        assert_eq!(
+            harness.mock.backend.clock.now(),
+            SystemTime::UNIX_EPOCH + Duration::from_millis(200)
+        );
+    });
+}
+
+#[test]
+fn pauses_while_waiting_for_the_next_iteration() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness
+            .start_with(
+                vec![press(Key::KeyA), release(Key::KeyA)],
+                player::PlaybackOptions {
+                    timing: player::Timing::AsFastAsPossible,
+                    repeat: player::Repeat::Times,
+                    repeat_count: 2,
+                    iteration_delay: Duration::from_millis(100),
+                    ..Default::default()
+                },
+            )
+            .await;
+        harness.run_until(Observed::Played(1)).await;
+        harness
+            .player
+            .perform_playback(harness.player_message_tx.clone())
+            .await;
+        assert_eq!(
+            harness.player.next_iteration_starts_at(),
+            Some(SystemTime::UNIX_EPOCH + Duration::from_millis(100))
+        );
+
+        harness
+            .player
+            .pause_playback(harness.player_message_tx.clone());
+        harness.pump().await;
+        assert_eq!(harness.observed.last(), Some(&Observed::Paused));
+        assert_eq!(harness.player.next_iteration_starts_at(), None);
+
+        harness.player.resume_playback();
+        harness.pump().await;
+        harness.run_to_end().await;
+
+        assert_eq!(
+            harness.observed,
+            [
+                Observed::Started,
+                Observed::Played(0),
+                Observed::Played(1),
+                Observed::Paused,
+                Observed::Resumed,
+                Observed::Iteration(2),
+                Observed::Played(0),
+                Observed::Played(1),
+                Observed::Done,
+            ]
+        );
+        assert_eq!(
+            harness.mock.backend.clock.now(),
+            SystemTime::UNIX_EPOCH + Duration::from_millis(100)
+        );
+    });
+}
+
+#[test]
+fn lets_every_iteration_through_the_grab() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness
+            .start_with(
+                vec![press(Key::KeyA), release(Key::KeyA)],
+                player::PlaybackOptions {
+                    timing: player::Timing::AsFastAsPossible,
+                    repeat: player::Repeat::Times,
+                    repeat_count: 2,
+                    ..Default::default()
+                },
+            )
+            .await;
+
+        let mut echoed = Vec::new();
+        for _ in 0..10 {
+            if harness.observed.last() == Some(&Observed::Done) {
+                break;
+            }
+            harness.step().await;
+            echoed.extend(harness.echo_simulated().await);
+        }
+
+        assert_eq!(
+            echoed,
+            [
+                (EventType::KeyPress(Key::KeyA), true),
+                (EventType::KeyRelease(Key::KeyA), true),
+                (EventType::KeyPress(Key::KeyA), true),
+                (EventType::KeyRelease(Key::KeyA), true),
+            ]
+        );
+        assert_eq!(harness.observed.last(), Some(&Observed::Done));
+    });
+}
+
+#[test]
+fn repeats_until_the_duration_is_elapsed() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness
+            .start_with(
+                vec![
+                    press(Key::KeyA),
+                    event(EventKind::Delay(Duration::from_millis(30))),
+                ],
+                player::PlaybackOptions {
+                    repeat: player::Repeat::For,
+                    repeat_duration: Duration::from_millis(100),
+                    ..Default::default()
+                },
+            )
+            .await;
+        harness.run_to_end().await;
+
+        assert_eq!(
+            harness
+                .observed
+                .iter()
+                .filter(|observed| matches!(observed, Observed::Iteration(_)))
+                .count(),
+            2
+        );
+    });
+}
+
+#[test]
+fn pausing_gives_input_back_until_resumed() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness
+            .start(vec![
+                press(Key::ShiftLeft),
+                press(Key::KeyA),
+                release(Key::KeyA),
+                release(Key::ShiftLeft),
+            ])
+            .await;
+        harness.step().await;
+        harness
+            .player
+            .pause_playback(harness.player_message_tx.clone());
+        harness.pump().await;
+
+        // Paused: steps do nothing and the user types freely
+        harness.step().await;
+        assert_eq!(harness.user_types(Key::KeyX).await, (true, true));
+
+        harness.player.resume_playback();
+        harness.pump().await;
+        harness.run_to_end().await;
+
+        assert_eq!(
+            harness.mock.journal(),
+            [
+                simulated(EventType::KeyPress(Key::ShiftLeft)),
+                simulated(EventType::KeyRelease(Key::ShiftLeft)),
+                simulated(EventType::KeyPress(Key::ShiftLeft)),
+                simulated(EventType::KeyPress(Key::KeyA)),
+                simulated(EventType::KeyRelease(Key::KeyA)),
+                simulated(EventType::KeyRelease(Key::ShiftLeft)),
+            ]
+        );
+        assert_eq!(
+            harness.observed,
+            [
+                Observed::Started,
+                Observed::Played(0),
+                Observed::Paused,
+                Observed::Resumed,
+                Observed::Played(1),
+                Observed::Played(2),
+                Observed::Played(3),
+                Observed::Done,
+            ]
+        );
+    });
+}
+
+#[test]
+fn releases_held_keys_on_pause_once_the_grab_is_released() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness
+            .start(vec![
+                press(Key::ShiftLeft),
+                press(Key::KeyA),
+                release(Key::KeyA),
+                release(Key::ShiftLeft),
+            ])
+            .await;
+        assert_eq!(
+            harness.step_echoing().await,
+            [(EventType::KeyPress(Key::ShiftLeft), true)]
+        );
+
+        harness
+            .player
+            .pause_playback(harness.player_message_tx.clone());
+        // Still grabbing, the release would be taken for user input
+        assert!(harness.echo_simulated().await.is_empty());
+        assert_eq!(
+            harness.echo_simulated().await,
+            [(EventType::KeyRelease(Key::ShiftLeft), true)]
+        );
+
+        harness.player.resume_playback();
+        harness.pump().await;
+        assert_eq!(
+            harness.run_to_end_echoing().await,
+            [
+                (EventType::KeyPress(Key::ShiftLeft), true),
+                (EventType::KeyPress(Key::KeyA), true),
+                (EventType::KeyRelease(Key::KeyA), true),
+                (EventType::KeyRelease(Key::ShiftLeft), true),
+            ]
+        );
+    });
+}
+
+#[test]
+fn only_acknowledges_pausing_a_running_playback() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness.player.initialize_playback(
+            vec![press(Key::KeyA), release(Key::KeyA)],
+            Default::default(),
+            Default::default(),
+            harness.listener_command_tx.clone(),
+        );
+        // Still waiting for the grab
+        harness
+            .player
+            .pause_playback(harness.player_message_tx.clone());
+        harness.pump().await;
+        harness.run_to_end().await;
+
+        assert_eq!(
+            harness.observed,
+            [
+                Observed::Started,
+                Observed::Played(0),
+                Observed::Played(1),
+                Observed::Done,
+            ]
+        );
+    });
+}
+
+#[test]
+fn pauses_on_breakpoints_and_steps_one_event_at_a_time() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness
+            .start_with_breakpoints(
+                vec![
+                    press(Key::KeyA),
+                    release(Key::KeyA),
+                    press(Key::KeyB),
+                    release(Key::KeyB),
+                    press(Key::KeyC),
+                    release(Key::KeyC),
+                ],
+                [1, 4].into(),
+            )
+            .await;
+        harness.run_until(Observed::Breakpoint(1)).await;
+
+        // The user gets the keyboard back, and KeyA is released while paused
+        harness.step().await;
+        assert_eq!(harness.user_types(Key::KeyX).await, (true, true));
+
+        harness.player.step_playback();
+        harness.pump().await;
+        harness.run_until(Observed::Breakpoint(2)).await;
+
+        harness.player.resume_playback();
+        harness.pump().await;
+        harness.run_until(Observed::Breakpoint(4)).await;
+
+        harness.player.resume_playback();
+        harness.pump().await;
+        harness.run_to_end().await;
+
+        assert_eq!(
+            harness.observed,
+            [
+                Observed::Started,
+                Observed::Played(0),
+                Observed::Breakpoint(1),
+                Observed::Resumed,
+                Observed::Played(1),
+                Observed::Breakpoint(2),
+                Observed::Resumed,
+                Observed::Played(2),
+                Observed::Played(3),
+                Observed::Breakpoint(4),
+                Observed::Resumed,
+                Observed::Played(4),
+                Observed::Played(5),
+                Observed::Done,
+            ]
+        );
+        assert_eq!(
+            harness.mock.journal(),
+            [
+                simulated(EventType::KeyPress(Key::KeyA)),
+                simulated(EventType::KeyRelease(Key::KeyA)),
+                simulated(EventType::KeyPress(Key::KeyA)),
+                simulated(EventType::KeyRelease(Key::KeyA)),
+                simulated(EventType::KeyPress(Key::KeyB)),
+                simulated(EventType::KeyRelease(Key::KeyB)),
+                simulated(EventType::KeyPress(Key::KeyC)),
+                simulated(EventType::KeyRelease(Key::KeyC)),
+            ]
+        );
+    });
+}
+
+#[test]
+fn releases_held_keys_at_breakpoints_once_the_grab_is_released() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness
+            .start_with_breakpoints(
+                vec![
+                    press(Key::ShiftLeft),
+                    press(Key::KeyA),
+                    release(Key::KeyA),
+                    release(Key::ShiftLeft),
+                ],
+                [2].into(),
+            )
+            .await;
+        assert_eq!(
+            harness.run_until_echoing(Observed::Breakpoint(2)).await,
+            [
+                (EventType::KeyPress(Key::ShiftLeft), true),
+                (EventType::KeyPress(Key::KeyA), true),
+            ]
+        );
+        assert_eq!(
+            harness.echo_simulated().await,
+            [
+                (EventType::KeyRelease(Key::KeyA), true),
+                (EventType::KeyRelease(Key::ShiftLeft), true),
+            ]
+        );
+
+        harness.player.resume_playback();
+        harness.pump().await;
+        assert_eq!(
+            harness.run_to_end_echoing().await,
+            [
+                (EventType::KeyPress(Key::ShiftLeft), true),
+                (EventType::KeyPress(Key::KeyA), true),
+                (EventType::KeyRelease(Key::KeyA), true),
+                (EventType::KeyRelease(Key::ShiftLeft), true),
+            ]
+        );
+    });
+}
+
+#[test]
+fn records_coalesced_mouse_moves_relative_to_the_focused_window() {
+    smol::block_on(async {
+        let mock = Mock::new("Editor");
+        mock.place_window("Editor", (100.0, 50.0));
+        let mut listener = listener::State::new(mock.backend.clone());
+        let (message_tx, mut message_rx) = channel(100);
+        listener
+            .handle_command(
+                listener::Command::ChangeMode(listener::Mode::Listen { record_mouse: true }),
+                message_tx.clone(),
+            )
+            .await;
+        listener
+            .on_focus_event("Editor".into(), message_tx.clone())
+            .await;
+
+        for (millis, event_type) in [
+            (0, EventType::MouseMove { x: 110.0, y: 60.0 }),
+            (10, EventType::MouseMove { x: 120.0, y: 70.0 }),
+            (20, EventType::MouseMove { x: 130.0, y: 80.0 }),
+            (30, EventType::ButtonPress(rdev::Button::Left)),
+            (40, EventType::ButtonRelease(rdev::Button::Left)),
+            (100, EventType::MouseMove { x: 90.0, y: 50.0 }),
+            (110, EventType::MouseMove { x: 95.0, y: 55.0 }),
+        ] {
+            let event = rdev::Event {
+                time: SystemTime::UNIX_EPOCH + Duration::from_millis(millis),
+                name: None,
+                event_type,
+            };
+            assert!(
+                listener
+                    .on_key_event(event, message_tx.clone())
+                    .await
+                    .is_some()
+            );
+        }
+        // Stopping the recording sends the move held back
+        listener
+            .handle_command(
+                listener::Command::ChangeMode(listener::Mode::Disabled),
+                message_tx.clone(),
+            )
+            .await;
+
+        let mut recorded = Vec::new();
+        while let Ok(Some(message)) = message_rx.try_next() {
+            if let listener::Message::Event(Event {
+                kind: kind @ (EventKind::Input(_) | EventKind::MouseMoveInWindow { .. }),
+                ..
+            }) = message
+            {
+                recorded.push(kind);
+            }
+        }
+        let in_window = |x, y, window_x, window_y| EventKind::MouseMoveInWindow {
+            x,
+            y,
+            window_x,
+            window_y,
+        };
+        assert_eq!(
+            recorded,
+            [
+                in_window(110.0, 60.0, 10.0, 10.0),
+                in_window(130.0, 80.0, 30.0, 30.0),
+                EventKind::Input(Input(EventType::ButtonPress(rdev::Button::Left))),
+                EventKind::Input(Input(EventType::ButtonRelease(rdev::Button::Left))),
+                in_window(90.0, 50.0, -10.0, 0.0),
+                in_window(95.0, 55.0, -5.0, 5.0),
+            ]
+        );
+    });
+}
+
+#[test]
+fn moves_the_mouse_relative_to_where_the_window_is_now() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness.mock.place_window("Target", (300.0, 200.0));
+        let in_window = |x, y, window_x, window_y| {
+            event(EventKind::MouseMoveInWindow {
+                x,
+                y,
+                window_x,
+                window_y,
+            })
+        };
+        harness
+            .start(vec![
+                focus("Target"),
+                in_window(110.0, 60.0, 10.0, 10.0),
+                focus("Closed"),
+                in_window(200.0, 100.0, 5.0, 5.0),
+            ])
+            .await;
+        harness.run_to_end().await;
+
+        assert_eq!(
+            harness.mock.journal(),
+            [
+                Action::Focus("Target".into()),
+                simulated(EventType::MouseMove { x: 310.0, y: 210.0 }),
+                Action::Focus("Closed".into()),
+                simulated(EventType::MouseMove { x: 200.0, y: 100.0 }),
+            ]
+        );
+    });
+}
+
+#[test]
+fn types_text_with_unicode_input_or_falls_back_to_keys() {
+    smol::block_on(async {
+        let text = || vec![event(EventKind::TypeText("Hé".into()))];
+        let mut harness = Harness::new("Editor");
+        harness.mock.enable_unicode_input();
+        harness.start(text()).await;
+        harness.run_to_end().await;
+
+        assert_eq!(
+            harness.mock.journal(),
+            [Action::TypeChar('H'), Action::TypeChar('é')]
+        );
+
+        // Without Unicode input, characters no key types are skipped
+        let mut harness = Harness::new("Editor");
+        harness.start(text()).await;
+        harness.run_to_end().await;
+
+        assert_eq!(
+            harness.mock.journal(),
+            [
+                simulated(EventType::KeyPress(Key::ShiftLeft)),
+                simulated(EventType::KeyPress(Key::KeyH)),
+                simulated(EventType::KeyRelease(Key::KeyH)),
+                simulated(EventType::KeyRelease(Key::ShiftLeft)),
+            ]
+        );
+        assert_eq!(
+            harness.mock.backend.clock.now(),
+            SystemTime::UNIX_EPOCH + Duration::from_millis(32)
+        );
+    });
+}
+
+#[test]
+fn lets_typed_text_through_the_grab() {
+    smol::block_on(async {
+        let events = || {
+            vec![
+                event(EventKind::TypeText("Hé".into())),
+                press(Key::KeyA),
+                release(Key::KeyA),
+            ]
+        };
+        let typed = |key| {
+            [
+                (EventType::KeyPress(key), true),
+                (EventType::KeyRelease(key), true),
+            ]
+        };
+        let mut harness = Harness::new("Editor");
+        harness.mock.enable_unicode_input();
+        harness.start(events()).await;
+
+        assert_eq!(
+            harness.run_to_end_echoing().await,
+            [
+                typed(Key::Unknown('H'.into())),
+                typed(Key::Unknown('é'.into())),
+                typed(Key::KeyA),
+            ]
+            .concat()
+        );
+
+        // Without Unicode input, the keys typing the text go through instead
+        let mut harness = Harness::new("Editor");
+        harness.start(events()).await;
+
+        assert_eq!(
+            harness.run_to_end_echoing().await,
+            [
+                (EventType::KeyPress(Key::ShiftLeft), true),
+                (EventType::KeyPress(Key::KeyH), true),
+                (EventType::KeyRelease(Key::KeyH), true),
+                (EventType::KeyRelease(Key::ShiftLeft), true),
+                (EventType::KeyPress(Key::KeyA), true),
+                (EventType::KeyRelease(Key::KeyA), true),
+            ]
+        );
+    });
+}
+
+#[test]
+fn plays_chords_as_ordered_key_events() {
+    smol::block_on(async {
+        let mut harness = Harness::new("Editor");
+        harness
+            .start(vec![event(EventKind::Chord {
+                modifiers: chord::Modifiers {
+                    alt: true,
+                    ..Default::default()
+                },
+                key: Key::F4,
+            })])
+            .await;
+        harness.run_to_end().await;
+
+        assert_eq!(
+            harness.mock.journal(),
+            [
+                simulated(EventType::KeyPress(Key::Alt)),
+                simulated(EventType::KeyPress(Key::F4)),
+                simulated(EventType::KeyRelease(Key::F4)),
+                simulated(EventType::KeyRelease(Key::Alt)),
+            ]
+        );
+    });
+}
@@ -0,0 +1,97 @@
+use std::collections::VecDeque;
+
+use crate::window::main::PrintableEvent;
+
+/// Number of steps kept for each macro, the oldest ones are forgotten first
+const HISTORY_SIZE: usize = 100;
+
+/// A reversible change of the item list of a macro. `breakpoints` are the indices,
+/// among `indices`, of the items having a breakpoint.
+#[derive(Debug, Clone)]
+pub enum Edit {
+    /// `items` inserted so that they end up at `indices`, sorted in increasing order
+    Insert {
+        indices: Vec<usize>,
+        items: Vec<PrintableEvent>,
+        breakpoints: Vec<usize>,
+    },
+    /// `items` removed from `indices`, sorted in increasing order
+    Remove {
+        indices: Vec<usize>,
+        items: Vec<PrintableEvent>,
+        breakpoints: Vec<usize>,
+    },
+}
+
+impl Edit {
+    fn inverse(self) -> Self {
+        match self {
+            Edit::Insert {
+                indices,
+                items,
+                breakpoints,
+            } => Edit::Remove {
+                indices,
+                items,
+                breakpoints,
+            },
+            Edit::Remove {
+                indices,
+                items,
+                breakpoints,
+            } => Edit::Insert {
+                indices,
+                items,
+                breakpoints,
+            },
+        }
+    }
+}
+
+/// Undo and redo stacks of a macro. A step holds the edits made by one user action,
+/// in the order they were applied.
+#[derive(Debug, Default)]
+pub struct History {
+    undo: VecDeque<Vec<Edit>>,
+    redo: Vec<Vec<Edit>>,
+}
+
+impl History {
+    /// Records a step that was just applied, which makes the undone steps unreachable
+    pub fn push(&mut self, mut step: Vec<Edit>) {
+        step.retain(|edit| match edit {
+            Edit::Insert { items, .. } | Edit::Remove { items, .. } => !items.is_empty(),
+        });
+        if step.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        self.undo.push_back(step);
+        if self.undo.len() > HISTORY_SIZE {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Edits reverting the last step, to apply in order
+    pub fn undo(&mut self) -> Option<Vec<Edit>> {
+        let step = self.undo.pop_back()?;
+        let inverse = step.iter().rev().cloned().map(Edit::inverse).collect();
+        self.redo.push(step);
+        Some(inverse)
+    }
+
+    /// Edits of the last undone step, to apply in order
+    pub fn redo(&mut self) -> Option<Vec<Edit>> {
+        let step = self.redo.pop()?;
+        self.undo.push_back(step.clone());
+        Some(step)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
@@ -0,0 +1,1128 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    ffi::OsString,
+    fmt::Display,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use iced::widget::scrollable::{self, Viewport};
+use itertools::Itertools;
+use log::error;
+
+use crate::{
+    key_name::{char_for_keystroke, key_to_name},
+    macro_file,
+    subscription::global_event::{
+        self, EventKind, Input,
+        chord::{Chord, ChordTracker},
+    },
+    window::main::{
+        ItemSelectionState, PlaybackRange, PrintableEvent,
+        history::{Edit, History},
+    },
+};
+
+const LIBRARY_DIRECTORY_ENV: &str = "POWERKEY_LIBRARY";
+const DEFAULT_MACRO_NAME: &str = "Untitled";
+const HOTKEY_METADATA_KEY: &str = "hotkey";
+
+/// A macro of the library along with the editing state the main window keeps for it
+pub struct Macro {
+    pub items: Vec<PrintableEvent>,
+    pub selected_items_state: ItemSelectionState,
+    /// Indices of the items playback pauses before
+    pub breakpoints: BTreeSet<usize>,
+    pub item_list_scroll_viewport: Option<Viewport>,
+    pub item_list_scroll_id: scrollable::Id,
+    pub file_path: PathBuf,
+    pub header: macro_file::Header,
+    pub dirty: bool,
+    pub hotkey_input: String,
+    pub history: History,
+    recording: Option<Recording>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingMode {
+    /// Recorded items replace the whole macro
+    #[default]
+    Replace,
+    /// Recorded items go after the selection, or at the end when nothing is selected
+    InsertAtCursor,
+    Append,
+}
+
+impl RecordingMode {
+    pub const ALL: [RecordingMode; 3] = [
+        RecordingMode::Replace,
+        RecordingMode::InsertAtCursor,
+        RecordingMode::Append,
+    ];
+}
+
+impl Display for RecordingMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordingMode::Replace => write!(f, "Record new"),
+            RecordingMode::InsertAtCursor => write!(f, "Record at cursor"),
+            RecordingMode::Append => write!(f, "Record at the end"),
+        }
+    }
+}
+
+/// Recording in progress in a macro
+#[derive(Debug)]
+struct Recording {
+    replaced_items: Vec<PrintableEvent>,
+    replaced_breakpoints: Vec<usize>,
+    /// Index of the first recorded item
+    start: usize,
+    /// Index the next recorded item goes to
+    next: usize,
+    /// Time the delay before the next recorded event counts from
+    previous_time: Option<SystemTime>,
+}
+
+impl Macro {
+    fn new(file_path: PathBuf, document: macro_file::Document) -> Self {
+        let hotkey_input = document
+            .header
+            .metadata
+            .get(HOTKEY_METADATA_KEY)
+            .cloned()
+            .unwrap_or_default();
+        Self {
+            items: document.events.into_iter().map(PrintableEvent).collect(),
+            selected_items_state: Default::default(),
+            breakpoints: Default::default(),
+            item_list_scroll_viewport: None,
+            item_list_scroll_id: scrollable::Id::unique(),
+            file_path,
+            header: document.header,
+            dirty: false,
+            hotkey_input,
+            history: Default::default(),
+            recording: None,
+        }
+    }
+
+    pub fn hotkey(&self) -> Option<Chord> {
+        let hotkey = self.header.metadata.get(HOTKEY_METADATA_KEY)?;
+        hotkey
+            .parse()
+            .inspect_err(|e| error!("Invalid hotkey for macro \"{}\": {e}", self.name()))
+            .ok()
+    }
+
+    pub fn set_hotkey(&mut self, hotkey: Option<Chord>) {
+        match hotkey {
+            Some(hotkey) => {
+                self.hotkey_input = hotkey.to_string();
+                self.header
+                    .metadata
+                    .insert(HOTKEY_METADATA_KEY.to_owned(), self.hotkey_input.clone());
+            }
+            None => {
+                self.hotkey_input.clear();
+                self.header.metadata.remove(HOTKEY_METADATA_KEY);
+            }
+        }
+        self.dirty = true;
+    }
+
+    /// Applies `edits` in order as one undoable step
+    fn edit(&mut self, edits: Vec<Edit>) {
+        for edit in &edits {
+            self.apply(edit);
+        }
+        self.history.push(edits);
+    }
+
+    /// Breakpoints stay on the items they were set on, go away with them and come back
+    /// with them
+    fn apply(&mut self, edit: &Edit) {
+        match edit {
+            Edit::Insert {
+                indices,
+                items,
+                breakpoints,
+            } => {
+                for (index, item) in indices.iter().zip(items) {
+                    self.items.insert(*index, item.clone());
+                    self.breakpoints = self
+                        .breakpoints
+                        .iter()
+                        .map(|breakpoint| {
+                            if breakpoint >= index {
+                                breakpoint + 1
+                            } else {
+                                *breakpoint
+                            }
+                        })
+                        .collect();
+                }
+                self.breakpoints.extend(breakpoints);
+            }
+            Edit::Remove { indices, .. } => {
+                for index in indices.iter().rev() {
+                    self.items.remove(*index);
+                }
+                self.breakpoints = self
+                    .breakpoints
+                    .iter()
+                    .filter(|breakpoint| !indices.contains(breakpoint))
+                    .map(|breakpoint| {
+                        breakpoint - indices.partition_point(|index| index < breakpoint)
+                    })
+                    .collect();
+            }
+        }
+        self.dirty = true;
+    }
+
+    fn removal(&self, indices: &BTreeSet<usize>) -> Edit {
+        Edit::Remove {
+            indices: indices.iter().cloned().collect(),
+            items: indices
+                .iter()
+                .map(|index| self.items[*index].clone())
+                .collect(),
+            breakpoints: self.breakpoints.intersection(indices).cloned().collect(),
+        }
+    }
+
+    /// Inserts `items` at `index`
+    pub fn insert_items(&mut self, index: usize, items: Vec<PrintableEvent>) {
+        self.edit(vec![Edit::Insert {
+            indices: (index..index + items.len()).collect(),
+            items,
+            breakpoints: Vec::new(),
+        }]);
+    }
+
+    /// Removes the items at `indices`
+    pub fn remove_items(&mut self, indices: &BTreeSet<usize>) {
+        let removal = self.removal(indices);
+        self.edit(vec![removal]);
+    }
+
+    /// Removes the items at `indices` and inserts `items` at `index`, counted after the removal.
+    /// `breakpoints` are the positions, within `items`, of the inserted items with a breakpoint.
+    pub fn replace_items(
+        &mut self,
+        indices: &BTreeSet<usize>,
+        index: usize,
+        items: Vec<PrintableEvent>,
+        breakpoints: impl IntoIterator<Item = usize>,
+    ) {
+        let removal = self.removal(indices);
+        self.edit(vec![
+            removal,
+            Edit::Insert {
+                indices: (index..index + items.len()).collect(),
+                items,
+                breakpoints: breakpoints
+                    .into_iter()
+                    .map(|position| index + position)
+                    .collect(),
+            },
+        ]);
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(edits) = self.history.undo() {
+            self.replay(&edits);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(edits) = self.history.redo() {
+            self.replay(&edits);
+        }
+    }
+
+    /// Applies `edits` outside of the history and selects what the last one changed
+    fn replay(&mut self, edits: &[Edit]) {
+        for edit in edits {
+            self.apply(edit);
+        }
+        match edits.last() {
+            Some(Edit::Insert { indices, .. }) => {
+                self.selected_items_state.unselect();
+                for index in indices {
+                    self.selected_items_state.add_item_to_selection(*index);
+                }
+            }
+            Some(Edit::Remove { indices, .. }) => match indices.first() {
+                Some(first) if !self.items.is_empty() => {
+                    self.selected_items_state
+                        .select((*first).min(self.items.len() - 1));
+                }
+                _ => self.selected_items_state.unselect(),
+            },
+            None => {}
+        }
+    }
+
+    /// Starts a recording, which is undone as a whole once finished. When recorded items
+    /// follow an existing item, that item counts as happening at `now`, so the first delay
+    /// is the wait for the first recorded event rather than the time since the item was recorded.
+    pub fn start_recording(&mut self, mode: RecordingMode, now: SystemTime) {
+        self.finish_recording();
+        let start = match mode {
+            RecordingMode::Replace => 0,
+            RecordingMode::InsertAtCursor => self
+                .selected_items_state
+                .get_last_selected()
+                .map_or(self.items.len(), |last_selected| {
+                    (last_selected + 1).min(self.items.len())
+                }),
+            RecordingMode::Append => self.items.len(),
+        };
+        let (replaced_items, replaced_breakpoints) = match mode {
+            RecordingMode::Replace => (
+                std::mem::take(&mut self.items),
+                std::mem::take(&mut self.breakpoints).into_iter().collect(),
+            ),
+            RecordingMode::InsertAtCursor | RecordingMode::Append => (Vec::new(), Vec::new()),
+        };
+        self.recording = Some(Recording {
+            replaced_items,
+            replaced_breakpoints,
+            start,
+            next: start,
+            previous_time: (start > 0).then_some(now),
+        });
+        self.selected_items_state.unselect();
+        self.dirty = true;
+    }
+
+    /// Inserts a recorded event, preceded by the delay since the previous one
+    pub fn record(&mut self, event: global_event::Event) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+        let mut items = Vec::new();
+        if let Some(previous_time) = recording.previous_time
+            && let Ok(delay) = event.time.duration_since(previous_time)
+        {
+            items.push(PrintableEvent(global_event::Event::new(
+                event.time,
+                EventKind::Delay(delay),
+            )));
+        }
+        recording.previous_time = Some(event.time);
+        items.push(PrintableEvent(event));
+        // Items may have been removed since the previous event
+        let index = recording.next.min(self.items.len());
+        recording.start = recording.start.min(index);
+        recording.next = index + items.len();
+        self.apply(&Edit::Insert {
+            indices: (index..index + items.len()).collect(),
+            items,
+            breakpoints: Vec::new(),
+        });
+    }
+
+    /// Records the recording started by [`Self::start_recording`] as one undoable step
+    pub fn finish_recording(&mut self) {
+        let Some(recording) = self.recording.take() else {
+            return;
+        };
+        let end = recording.next.min(self.items.len());
+        let start = recording.start.min(end);
+        self.history.push(vec![
+            Edit::Remove {
+                indices: (0..recording.replaced_items.len()).collect(),
+                items: recording.replaced_items,
+                breakpoints: recording.replaced_breakpoints,
+            },
+            Edit::Insert {
+                indices: (start..end).collect(),
+                items: self.items[start..end].to_vec(),
+                breakpoints: Vec::new(),
+            },
+        ]);
+    }
+
+    /// Changes the kind of the item at `index`, keeping its time and breakpoint
+    pub fn edit_item(&mut self, index: usize, kind: EventKind) {
+        let time = self.items[index].0.time;
+        let breakpoint = self.breakpoints.contains(&index);
+        self.replace_items(
+            &[index].into(),
+            index,
+            vec![PrintableEvent(global_event::Event::new(time, kind))],
+            breakpoint.then_some(0),
+        );
+    }
+
+    /// Moves the selected items next to the item at `target`: before it when it is above
+    /// the selection, after it otherwise. Selection and breakpoints follow the moved items.
+    pub fn move_selection_onto(&mut self, target: usize) {
+        let selected = self
+            .selected_items_state
+            .iter()
+            .filter(|index| *index < self.items.len())
+            .collect::<BTreeSet<_>>();
+        let Some(first) = selected.first().cloned() else {
+            return;
+        };
+        if target >= self.items.len() || selected.contains(&target) {
+            return;
+        }
+        let remaining_before_target = target - selected.range(..target).count();
+        let index = if target < first {
+            remaining_before_target
+        } else {
+            remaining_before_target + 1
+        };
+        let items = selected
+            .iter()
+            .map(|index| self.items[*index].clone())
+            .collect_vec();
+        let moved_breakpoints = selected
+            .iter()
+            .positions(|index| self.breakpoints.contains(index))
+            .collect_vec();
+        let count = items.len();
+        self.replace_items(&selected, index, items, moved_breakpoints);
+        self.selected_items_state.unselect();
+        for moved_index in index..index + count {
+            self.selected_items_state.add_item_to_selection(moved_index);
+        }
+    }
+
+    /// Replaces the consecutive selected items with a single item of the kind `merge` makes
+    /// out of them
+    fn merge_selection(
+        &mut self,
+        merge: impl FnOnce(&[PrintableEvent]) -> Result<EventKind, String>,
+    ) -> Result<(), String> {
+        let selected = self.selected_items_state.iter().collect::<BTreeSet<_>>();
+        let (Some(first), Some(last)) = (selected.first().cloned(), selected.last().cloned())
+        else {
+            return Err("Select the items to merge first".into());
+        };
+        if last - first + 1 != selected.len() {
+            return Err("Only consecutive items can be merged".into());
+        }
+        let kind = merge(&self.items[first..=last])?;
+        let time = self.items[first].0.time;
+        self.replace_items(
+            &selected,
+            first,
+            vec![PrintableEvent(global_event::Event::new(time, kind))],
+            None,
+        );
+        self.selected_items_state.select(first);
+        Ok(())
+    }
+
+    /// Replaces the selected items, key strokes and the delays between them,
+    /// with a single item typing the same text
+    pub fn collapse_selection_into_text(&mut self) -> Result<(), String> {
+        self.merge_selection(|items| text_typed_by(items).map(EventKind::TypeText))
+    }
+
+    /// Replaces the selected items, a key pressed while holding modifiers and the delays
+    /// between them, with a single chord item
+    pub fn convert_selection_into_chord(&mut self) -> Result<(), String> {
+        self.merge_selection(|items| {
+            chord_pressed_by(items)
+                .map(|Chord { modifiers, key }| EventKind::Chord { modifiers, key })
+        })
+    }
+
+    /// Sets a breakpoint on every selected item, or clears them if they all have one.
+    /// Breakpoints are not saved, so this leaves the macro unchanged.
+    pub fn toggle_breakpoints(&mut self) {
+        let selected = self.selected_items_state.iter().collect::<BTreeSet<_>>();
+        if selected.is_subset(&self.breakpoints) {
+            self.breakpoints.retain(|index| !selected.contains(index));
+        } else {
+            self.breakpoints.extend(selected);
+        }
+    }
+
+    pub fn name(&self) -> String {
+        macro_file::name_from_path(&self.file_path)
+    }
+
+    /// Indices of the items to play. When the first one is not a focus change, the focus
+    /// change preceding it is played first so the events still go to the right window.
+    pub fn playback_indices(&self, range: PlaybackRange) -> Vec<usize> {
+        let first_selected = self.selected_items_state.get_first_selected();
+        let mut indices = match (range, first_selected) {
+            (PlaybackRange::FromCursor, Some(first_selected)) => {
+                (first_selected..self.items.len()).collect_vec()
+            }
+            (PlaybackRange::Selection, Some(_)) => self
+                .selected_items_state
+                .iter()
+                .filter(|index| *index < self.items.len())
+                .collect_vec(),
+            _ => (0..self.items.len()).collect_vec(),
+        };
+        let is_focus_change =
+            |index: usize| matches!(self.items[index].0.kind, EventKind::FocusChange { .. });
+        if let Some(first) = indices.first().cloned()
+            && !is_focus_change(first)
+            && let Some(focus_change) = (0..first).rev().find(|index| is_focus_change(*index))
+        {
+            indices.insert(0, focus_change);
+        }
+        indices
+    }
+
+    /// Indices, within `playback_indices`, of the events to pause before
+    pub fn playback_breakpoints(&self, playback_indices: &[usize]) -> BTreeSet<usize> {
+        playback_indices
+            .iter()
+            .positions(|index| self.breakpoints.contains(index))
+            .collect()
+    }
+
+    pub fn document(&self) -> macro_file::Document {
+        macro_file::Document {
+            header: self.header.clone(),
+            events: self.items.iter().map(|event| event.0.clone()).collect_vec(),
+        }
+    }
+}
+
+/// Text typed by key strokes on a US QWERTY layout, every key pressed must be released
+fn text_typed_by(items: &[PrintableEvent]) -> Result<String, String> {
+    let mut text = String::new();
+    let mut held_keys = HashSet::new();
+    for item in items {
+        match &item.0.kind {
+            EventKind::Input(Input(rdev::EventType::KeyPress(key))) => {
+                held_keys.insert(*key);
+                if matches!(key, rdev::Key::ShiftLeft | rdev::Key::ShiftRight) {
+                    continue;
+                }
+                let shift = held_keys.contains(&rdev::Key::ShiftLeft)
+                    || held_keys.contains(&rdev::Key::ShiftRight);
+                let c = char_for_keystroke(*key, shift)
+                    .ok_or_else(|| format!("{} does not type a character", key_to_name(*key)))?;
+                text.push(c);
+            }
+            EventKind::Input(Input(rdev::EventType::KeyRelease(key))) => {
+                held_keys.remove(key);
+            }
+            EventKind::TypeText(typed) => text.push_str(typed),
+            EventKind::Delay(_) => {}
+            _ => return Err("Only key strokes and delays can be collapsed into text".into()),
+        }
+    }
+    all_released(&held_keys)?;
+    Ok(text)
+}
+
+/// Chord pressed by key strokes, every key pressed must be released
+fn chord_pressed_by(items: &[PrintableEvent]) -> Result<Chord, String> {
+    let mut tracker = ChordTracker::default();
+    let mut chord = None;
+    let mut held_keys = HashSet::new();
+    for item in items {
+        match &item.0.kind {
+            EventKind::Input(Input(event_type)) => {
+                match event_type {
+                    rdev::EventType::KeyPress(key) => held_keys.insert(*key),
+                    rdev::EventType::KeyRelease(key) => held_keys.remove(key),
+                    _ => {
+                        return Err(
+                            "Only key strokes and delays can be converted into a chord".into()
+                        );
+                    }
+                };
+                if let Some(pressed) = tracker.on_event(event_type)
+                    && chord.replace(pressed).is_some()
+                {
+                    return Err("A chord presses a single key besides its modifiers".into());
+                }
+            }
+            EventKind::Delay(_) => {}
+            _ => return Err("Only key strokes and delays can be converted into a chord".into()),
+        }
+    }
+    all_released(&held_keys)?;
+    chord.ok_or_else(|| "Only modifiers are pressed in the selection".into())
+}
+
+fn all_released(held_keys: &HashSet<rdev::Key>) -> Result<(), String> {
+    match held_keys.iter().next() {
+        Some(key) => Err(format!(
+            "{} is still held at the end of the selection",
+            key_to_name(*key)
+        )),
+        None => Ok(()),
+    }
+}
+
+pub struct Library {
+    pub directory: PathBuf,
+    pub macros: Vec<Macro>,
+    pub current: usize,
+}
+
+impl Library {
+    /// `POWERKEY_LIBRARY` if set, otherwise a `macros` folder in the user data directory
+    pub fn default_directory() -> PathBuf {
+        if let Some(directory) = std::env::var_os(LIBRARY_DIRECTORY_ENV) {
+            return directory.into();
+        }
+        dirs::data_dir()
+            .map(|directory| directory.join("powerkey"))
+            .unwrap_or_else(|| PathBuf::from("powerkey"))
+            .join("macros")
+    }
+
+    /// Loads every macro of `directory`, skipping the ones that fail to load.
+    /// The library is never empty: an untitled macro is created when nothing could be loaded.
+    pub fn load(directory: PathBuf) -> Self {
+        if let Err(e) = std::fs::create_dir_all(&directory) {
+            error!(
+                "Could not create macro library {}: {e}",
+                directory.display()
+            );
+        }
+        let paths = macro_file::list(&directory).unwrap_or_else(|e| {
+            error!("Could not list macro library {}: {e}", directory.display());
+            Vec::new()
+        });
+        let macros = paths
+            .into_iter()
+            .filter_map(|path| match macro_file::load(&path) {
+                Ok(document) => Some(Macro::new(path, document)),
+                Err(e) => {
+                    error!("Could not load {}: {e}", path.display());
+                    None
+                }
+            })
+            .collect();
+        let mut library = Self {
+            directory,
+            macros,
+            current: 0,
+        };
+        if library.macros.is_empty() {
+            library.create(DEFAULT_MACRO_NAME, Vec::new());
+        }
+        library
+    }
+
+    pub fn current(&self) -> &Macro {
+        &self.macros[self.current]
+    }
+
+    pub fn current_mut(&mut self) -> &mut Macro {
+        &mut self.macros[self.current]
+    }
+
+    /// The macro a recording is in progress in, if any
+    pub fn recording_mut(&mut self) -> Option<&mut Macro> {
+        self.macros
+            .iter_mut()
+            .find(|macro_| macro_.recording.is_some())
+    }
+
+    /// Ends the recording in progress, whichever macro it is in
+    pub fn finish_recording(&mut self) {
+        for macro_ in &mut self.macros {
+            macro_.finish_recording();
+        }
+    }
+
+    pub fn position(&self, path: &Path) -> Option<usize> {
+        self.macros
+            .iter()
+            .position(|macro_| macro_.file_path == path)
+    }
+
+    /// The extension is appended rather than set, so that dots in `name` are kept
+    fn path_for(&self, name: &str) -> PathBuf {
+        self.directory
+            .join(format!("{name}.{}", macro_file::EXTENSION))
+    }
+
+    fn is_name_taken(&self, name: &str) -> bool {
+        self.path_for(name).exists()
+            || self
+                .macros
+                .iter()
+                .any(|macro_| macro_.name().eq_ignore_ascii_case(name))
+    }
+
+    /// `base`, or `base` followed by the first free number
+    fn unique_name(&self, base: &str) -> String {
+        (1..)
+            .map(|number| match number {
+                1 => base.to_owned(),
+                number => format!("{base} {number}"),
+            })
+            .find(|name| !self.is_name_taken(name))
+            .unwrap()
+    }
+
+    /// Adds a macro saved in the library directory, makes it current and returns its index
+    pub fn create(&mut self, name: &str, events: Vec<global_event::Event>) -> usize {
+        let name = self.unique_name(name);
+        let file_path = self.path_for(&name);
+        let document = macro_file::Document {
+            header: macro_file::Header::new(name),
+            events,
+        };
+        let mut macro_ = Macro::new(file_path, document);
+        if let Err(e) = macro_file::save(&macro_.file_path, &macro_.document()) {
+            error!("Could not save {}: {e}", macro_.file_path.display());
+            macro_.dirty = true;
+        }
+        self.macros.push(macro_);
+        self.current = self.macros.len() - 1;
+        self.current
+    }
+
+    pub fn open(&mut self, path: PathBuf, document: macro_file::Document) -> usize {
+        self.macros.push(Macro::new(path, document));
+        self.current = self.macros.len() - 1;
+        self.current
+    }
+
+    pub fn duplicate_current(&mut self) -> usize {
+        let current = self.current();
+        let name = format!("{} copy", current.name());
+        let events = current.items.iter().map(|event| event.0.clone()).collect();
+        self.create(&name, events)
+    }
+
+    /// Renames the file of the current macro, keeping its extension
+    pub fn rename_current(&mut self, name: &str) -> Result<(), String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Macro name can't be empty".into());
+        }
+        if name.contains(['/', '\\']) {
+            return Err(format!(
+                "Macro name \"{name}\" can't contain a path separator"
+            ));
+        }
+        let current = self.current();
+        if current.name() == name {
+            return Ok(());
+        }
+        if !current.name().eq_ignore_ascii_case(name) && self.is_name_taken(name) {
+            return Err(format!("A macro named \"{name}\" already exists"));
+        }
+        let mut file_name = OsString::from(name);
+        if let Some(extension) = current.file_path.extension() {
+            file_name.push(".");
+            file_name.push(extension);
+        }
+        let file_path = current.file_path.with_file_name(file_name);
+        if current.file_path.exists() {
+            std::fs::rename(&current.file_path, &file_path).map_err(|e| {
+                format!(
+                    "Could not rename {} to {}: {e}",
+                    current.file_path.display(),
+                    file_path.display()
+                )
+            })?;
+        }
+        let current = self.current_mut();
+        current.file_path = file_path;
+        current.header.name = name.to_owned();
+        Ok(())
+    }
+
+    /// Removes the current macro and its file, selecting its neighbour
+    pub fn delete_current(&mut self) {
+        let removed = self.macros.remove(self.current);
+        if removed.file_path.exists()
+            && let Err(e) = std::fs::remove_file(&removed.file_path)
+        {
+            error!("Could not delete {}: {e}", removed.file_path.display());
+        }
+        if self.macros.is_empty() {
+            self.create(DEFAULT_MACRO_NAME, Vec::new());
+        } else {
+            self.current = self.current.min(self.macros.len() - 1);
+        }
+    }
+
+    pub fn dirty_macros(&self) -> impl Iterator<Item = &Macro> {
+        self.macros.iter().filter(|macro_| macro_.dirty)
+    }
+
+    /// Index of the macro bound to `hotkey`
+    pub fn find_by_hotkey(&self, hotkey: Chord) -> Option<usize> {
+        self.macros
+            .iter()
+            .position(|macro_| macro_.hotkey() == Some(hotkey))
+    }
+
+    pub fn hotkeys(&self) -> HashSet<Chord> {
+        self.macros.iter().filter_map(Macro::hotkey).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rdev::{EventType, Key};
+
+    use super::*;
+    use crate::subscription::global_event::{Event, Input};
+
+    fn macro_with(kinds: Vec<EventKind>) -> Macro {
+        Macro::new(
+            PathBuf::from("test.json"),
+            macro_file::Document {
+                header: macro_file::Header::new("test"),
+                events: kinds
+                    .into_iter()
+                    .map(|kind| Event::new(SystemTime::UNIX_EPOCH, kind))
+                    .collect(),
+            },
+        )
+    }
+
+    fn press() -> EventKind {
+        EventKind::Input(Input(EventType::KeyPress(Key::KeyA)))
+    }
+
+    fn focus() -> EventKind {
+        EventKind::FocusChange {
+            window_title: "Notepad".into(),
+        }
+    }
+
+    #[test]
+    fn plays_from_cursor_after_the_preceding_focus_change() {
+        let mut macro_ = macro_with(vec![focus(), press(), focus(), press(), press()]);
+        macro_.selected_items_state.select(3);
+
+        assert_eq!(macro_.playback_indices(PlaybackRange::All), [0, 1, 2, 3, 4]);
+        assert_eq!(
+            macro_.playback_indices(PlaybackRange::FromCursor),
+            [2, 3, 4]
+        );
+        assert_eq!(macro_.playback_indices(PlaybackRange::Selection), [2, 3]);
+
+        macro_.selected_items_state.select(2);
+
+        assert_eq!(macro_.playback_indices(PlaybackRange::Selection), [2]);
+    }
+
+    #[test]
+    fn plays_the_whole_selection_in_order() {
+        let mut macro_ = macro_with(vec![press(), press(), press(), press()]);
+        macro_.selected_items_state.select(3);
+        macro_.selected_items_state.add_item_to_selection(1);
+
+        assert_eq!(macro_.playback_indices(PlaybackRange::Selection), [1, 3]);
+        assert_eq!(
+            macro_.playback_indices(PlaybackRange::FromCursor),
+            [1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn breakpoints_follow_their_items() {
+        let mut macro_ = macro_with(vec![press(), press(), press(), press()]);
+        macro_.breakpoints = [1, 3].into();
+
+        macro_.insert_items(
+            2,
+            vec![PrintableEvent(Event::new(SystemTime::UNIX_EPOCH, focus()))],
+        );
+        assert_eq!(macro_.breakpoints, [1, 4].into());
+
+        macro_.remove_items(&[0, 1].into());
+        assert_eq!(macro_.breakpoints, [2].into());
+        assert_eq!(macro_.playback_breakpoints(&[0, 2, 3]), [1].into());
+    }
+
+    #[test]
+    fn toggles_breakpoints_without_changing_the_macro() {
+        let mut macro_ = macro_with(vec![press(), press(), press()]);
+        macro_.selected_items_state.select(0);
+        macro_.selected_items_state.add_item_to_selection(2);
+
+        macro_.toggle_breakpoints();
+        assert_eq!(macro_.breakpoints, [0, 2].into());
+        macro_.selected_items_state.select(2);
+        macro_.toggle_breakpoints();
+        assert_eq!(macro_.breakpoints, [0].into());
+        assert!(!macro_.dirty);
+        assert!(!macro_.history.can_undo());
+    }
+
+    #[test]
+    fn edits_an_item_keeping_its_breakpoint() {
+        let mut macro_ = macro_with(vec![press(), press()]);
+        macro_.breakpoints = [1].into();
+
+        macro_.edit_item(1, focus());
+        assert_eq!(macro_.items[1].0.kind, focus());
+        assert_eq!(macro_.breakpoints, [1].into());
+
+        macro_.undo();
+        assert_eq!(macro_.items[1].0.kind, press());
+        assert_eq!(macro_.breakpoints, [1].into());
+
+        macro_.redo();
+        assert_eq!(macro_.items[1].0.kind, focus());
+        assert_eq!(macro_.breakpoints, [1].into());
+    }
+
+    #[test]
+    fn moves_the_selection_around_the_target_item() {
+        let delay = |millis| EventKind::Delay(std::time::Duration::from_millis(millis));
+        let delays = |macro_: &Macro| {
+            macro_
+                .items
+                .iter()
+                .map(|item| match item.0.kind {
+                    EventKind::Delay(delay) => delay.as_millis(),
+                    _ => unreachable!(),
+                })
+                .collect_vec()
+        };
+        let mut macro_ = macro_with((0..5).map(delay).collect());
+        macro_.selected_items_state.select(1);
+        macro_.selected_items_state.add_item_to_selection(3);
+        macro_.breakpoints = [3].into();
+
+        macro_.move_selection_onto(4);
+        assert_eq!(delays(&macro_), [0, 2, 4, 1, 3]);
+        assert_eq!(macro_.selected_items_state.iter().collect_vec(), [3, 4]);
+        assert_eq!(macro_.breakpoints, [4].into());
+
+        macro_.undo();
+        assert_eq!(delays(&macro_), [0, 1, 2, 3, 4]);
+        assert_eq!(macro_.breakpoints, [3].into());
+        macro_.redo();
+        assert_eq!(delays(&macro_), [0, 2, 4, 1, 3]);
+        assert_eq!(macro_.breakpoints, [4].into());
+
+        macro_.move_selection_onto(0);
+        assert_eq!(delays(&macro_), [1, 3, 0, 2, 4]);
+
+        macro_.move_selection_onto(1);
+        assert_eq!(delays(&macro_), [1, 3, 0, 2, 4]);
+    }
+
+    #[test]
+    fn inserts_recordings_timed_from_their_start() {
+        let at = |millis| SystemTime::UNIX_EPOCH + std::time::Duration::from_millis(millis);
+        let delay = |millis| EventKind::Delay(std::time::Duration::from_millis(millis));
+        let mut macro_ = macro_with(vec![focus(), press()]);
+        macro_.selected_items_state.select(0);
+
+        macro_.start_recording(RecordingMode::InsertAtCursor, at(10_000));
+        macro_.record(Event::new(at(10_300), EventKind::YieldFocus));
+        macro_.record(Event::new(at(10_350), press()));
+        macro_.finish_recording();
+
+        let kinds = macro_
+            .items
+            .iter()
+            .map(|item| item.0.kind.clone())
+            .collect_vec();
+        assert_eq!(
+            kinds,
+            [
+                focus(),
+                delay(300),
+                EventKind::YieldFocus,
+                delay(50),
+                press(),
+                press()
+            ]
+        );
+
+        macro_.undo();
+        assert_eq!(macro_.items.len(), 2);
+
+        macro_.start_recording(RecordingMode::Append, at(20_000));
+        macro_.record(Event::new(at(20_100), press()));
+        macro_.finish_recording();
+        assert_eq!(macro_.items[2].0.kind, delay(100));
+    }
+
+    #[test]
+    fn keeps_recording_after_items_are_removed() {
+        let at = |millis| SystemTime::UNIX_EPOCH + std::time::Duration::from_millis(millis);
+        let delay = |millis| EventKind::Delay(std::time::Duration::from_millis(millis));
+        let mut macro_ = macro_with(vec![focus(), press()]);
+
+        macro_.start_recording(RecordingMode::Append, at(10_000));
+        macro_.record(Event::new(at(10_100), press()));
+        macro_.remove_items(&[0, 1, 2, 3].into());
+        macro_.record(Event::new(at(10_150), EventKind::YieldFocus));
+        macro_.finish_recording();
+
+        let kinds = macro_
+            .items
+            .iter()
+            .map(|item| item.0.kind.clone())
+            .collect_vec();
+        assert_eq!(kinds, [delay(50), EventKind::YieldFocus]);
+        macro_.undo();
+        assert!(macro_.items.is_empty());
+    }
+
+    #[test]
+    fn collapses_key_strokes_into_text() {
+        let key = |event_type| EventKind::Input(Input(event_type));
+        let mut macro_ = macro_with(vec![
+            focus(),
+            key(EventType::KeyPress(Key::ShiftRight)),
+            key(EventType::KeyPress(Key::KeyH)),
+            key(EventType::KeyRelease(Key::KeyH)),
+            key(EventType::KeyRelease(Key::ShiftRight)),
+            EventKind::Delay(std::time::Duration::from_millis(80)),
+            key(EventType::KeyPress(Key::KeyI)),
+            key(EventType::KeyRelease(Key::KeyI)),
+            key(EventType::KeyPress(Key::Num1)),
+        ]);
+        macro_.selected_items_state.select(0);
+        macro_.selected_items_state.expand_to(7);
+        assert!(macro_.collapse_selection_into_text().is_err());
+
+        macro_.selected_items_state.select(1);
+        macro_.selected_items_state.expand_to(8);
+        assert!(macro_.collapse_selection_into_text().is_err());
+
+        macro_.selected_items_state.select(1);
+        macro_.selected_items_state.expand_to(7);
+        macro_.collapse_selection_into_text().unwrap();
+
+        assert_eq!(
+            macro_
+                .items
+                .iter()
+                .map(|item| item.0.kind.clone())
+                .collect_vec(),
+            [
+                focus(),
+                EventKind::TypeText("Hi".into()),
+                key(EventType::KeyPress(Key::Num1)),
+            ]
+        );
+        assert!(macro_.selected_items_state.is_selected(1));
+    }
+
+    #[test]
+    fn converts_a_key_press_with_modifiers_into_a_chord() {
+        let key = |event_type| EventKind::Input(Input(event_type));
+        let mut macro_ = macro_with(vec![
+            key(EventType::KeyPress(Key::ControlLeft)),
+            key(EventType::KeyPress(Key::ShiftRight)),
+            EventKind::Delay(std::time::Duration::from_millis(30)),
+            key(EventType::KeyPress(Key::KeyS)),
+            key(EventType::KeyRelease(Key::KeyS)),
+            key(EventType::KeyRelease(Key::ControlLeft)),
+            key(EventType::KeyRelease(Key::ShiftRight)),
+            key(EventType::KeyPress(Key::KeyS)),
+            key(EventType::KeyRelease(Key::KeyS)),
+        ]);
+        macro_.selected_items_state.select(0);
+        macro_.selected_items_state.expand_to(8);
+        assert!(macro_.convert_selection_into_chord().is_err());
+
+        macro_.selected_items_state.select(0);
+        macro_.selected_items_state.expand_to(6);
+        macro_.convert_selection_into_chord().unwrap();
+
+        assert_eq!(macro_.items.len(), 3);
+        assert_eq!(
+            macro_.items[0].0.kind,
+            EventKind::Chord {
+                modifiers: "Ctrl+Shift+s".parse::<Chord>().unwrap().modifiers,
+                key: Key::KeyS,
+            }
+        );
+    }
+
+    #[test]
+    fn undoes_and_redoes_every_edit() {
+        let kinds = |macro_: &Macro| {
+            macro_
+                .items
+                .iter()
+                .map(|item| item.0.kind.clone())
+                .collect_vec()
+        };
+        let mut macro_ = macro_with(vec![focus(), press()]);
+        macro_.breakpoints = [1].into();
+
+        macro_.start_recording(RecordingMode::Replace, SystemTime::UNIX_EPOCH);
+        macro_.record(Event::new(SystemTime::UNIX_EPOCH, press()));
+        macro_.finish_recording();
+        macro_.insert_items(
+            0,
+            vec![PrintableEvent(Event::new(
+                SystemTime::UNIX_EPOCH,
+                EventKind::YieldFocus,
+            ))],
+        );
+        assert_eq!(kinds(&macro_), [EventKind::YieldFocus, press()]);
+
+        macro_.undo();
+        assert_eq!(kinds(&macro_), [press()]);
+        macro_.undo();
+        assert_eq!(kinds(&macro_), [focus(), press()]);
+        assert!(!macro_.history.can_undo());
+
+        macro_.redo();
+        macro_.remove_items(&[0].into());
+        assert!(!macro_.history.can_redo());
+        macro_.undo();
+        assert_eq!(kinds(&macro_), [press()]);
+        assert!(macro_.selected_items_state.is_selected(0));
+    }
+
+    #[test]
+    fn forgets_the_oldest_steps() {
+        let mut macro_ = macro_with(Vec::new());
+        for _ in 0..150 {
+            macro_.insert_items(
+                0,
+                vec![PrintableEvent(Event::new(SystemTime::UNIX_EPOCH, press()))],
+            );
+        }
+        while macro_.history.can_undo() {
+            macro_.undo();
+        }
+
+        assert_eq!(macro_.items.len(), 50);
+    }
+
+    #[test]
+    fn keeps_dots_in_macro_names() {
+        let directory =
+            std::env::temp_dir().join(format!("powerkey-library-{}", std::process::id()));
+        let mut library = Library::load(directory.clone());
+        library.create("v1", Vec::new());
+        library.create("v1.2 login", Vec::new());
+        let created = library.current().file_path.clone();
+        let renamed = library.rename_current("v1.3 login");
+        let files = macro_file::list(&directory);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(created, directory.join("v1.2 login.json"));
+        assert!(renamed.is_ok());
+        assert_eq!(library.current().name(), "v1.3 login");
+        assert_eq!(
+            files.unwrap(),
+            [
+                directory.join("Untitled.json"),
+                directory.join("v1.3 login.json"),
+                directory.join("v1.json"),
+            ]
+        );
+    }
+}
@@ -20,6 +20,17 @@ impl From<subscription::global_event::listener::Message> for Message {
                     GlobalEventTrigger::ListenerAddGrabIgnoreListDone,
                 ))
             }
+            subscription::global_event::listener::Message::HotkeyPressed(chord) => {
+                Message::Trigger(Trigger::GlobalEvent(GlobalEventTrigger::HotkeyPressed(
+                    chord,
+                )))
+            }
+            subscription::global_event::listener::Message::PanicHotkeyPressed => {
+                Message::Trigger(Trigger::GlobalEvent(GlobalEventTrigger::PanicHotkeyPressed))
+            }
+            subscription::global_event::listener::Message::KeyCaptured(chord) => {
+                Message::Trigger(Trigger::GlobalEvent(GlobalEventTrigger::KeyCaptured(chord)))
+            }
         }
     }
 }
@@ -39,6 +50,23 @@ impl From<subscription::global_event::player::Message> for Message {
             subscription::global_event::player::Message::PlaybackJustStarted => Message::Trigger(
                 Trigger::GlobalEvent(GlobalEventTrigger::PlayerPlaybackJustStarted),
             ),
+            subscription::global_event::player::Message::BreakpointHit { index } => {
+                Message::Trigger(Trigger::GlobalEvent(
+                    GlobalEventTrigger::PlayerBreakpointHit(index),
+                ))
+            }
+            subscription::global_event::player::Message::PlaybackJustPaused => Message::Trigger(
+                Trigger::GlobalEvent(GlobalEventTrigger::PlayerPlaybackJustPaused),
+            ),
+            subscription::global_event::player::Message::PlaybackJustResumed => Message::Trigger(
+                Trigger::GlobalEvent(GlobalEventTrigger::PlayerPlaybackJustResumed),
+            ),
+            subscription::global_event::player::Message::IterationJustStarted {
+                iteration,
+                total,
+            } => Message::Trigger(Trigger::GlobalEvent(
+                GlobalEventTrigger::PlayerIterationJustStarted { iteration, total },
+            )),
         }
     }
 }
@@ -1,39 +1,64 @@
-use std::{collections::BTreeSet, fmt::Display, time::SystemTime};
+use std::{
+    collections::BTreeSet,
+    fmt::Display,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
 
 use iced::{
-    Element, Length, Subscription, Task, Theme,
+    Border, Element, Length, Subscription, Task, Theme,
     event::Status,
     futures::channel::mpsc::Sender,
     keyboard::{Key, Modifiers, key::Named},
     widget::{
-        self, button, checkbox, column, container, mouse_area, row,
+        self, button, checkbox, column, container, mouse_area, pick_list, row,
         scrollable::{AbsoluteOffset, Viewport},
-        text,
+        slider, text, text_input,
     },
     window::Level,
 };
 use itertools::Itertools;
-use log::trace;
+use log::{error, trace};
 use rdev::EventType;
+use rfd::{AsyncFileDialog, AsyncMessageDialog, MessageButtons, MessageDialogResult, MessageLevel};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     custom_widget::separator::separator,
-    subscription::global_event::{self, Input, player},
+    macro_file,
+    subscription::global_event::{
+        self, Input,
+        chord::{self, Chord},
+        player::{self, PlaybackOptions, Repeat, Timing},
+    },
     utils::{OrdPairExt, SenderOption, SubscriptionExt},
 };
 
+mod history;
+mod library;
 mod mapper;
 
+use library::{Library, RecordingMode};
+
 #[derive(Default, Debug)]
 enum PlaybackMode {
     #[default]
     Idle,
     PlayerWaitsForGrab,
     Play,
+    Paused,
     Record,
 }
 
+/// Which items of the current macro a playback goes through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackRange {
+    All,
+    /// From the first selected item to the end
+    FromCursor,
+    Selection,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct PrintableEvent(global_event::Event);
 
@@ -43,8 +68,33 @@ impl Display for PrintableEvent {
             global_event::EventKind::Input(Input(event)) => match event {
                 EventType::KeyPress(key) => write!(f, "Press {key:?}"),
                 EventType::KeyRelease(key) => write!(f, "Release {key:?}"),
-                _ => unreachable!("mouse event not supported"),
+                EventType::ButtonPress(button) => write!(f, "Press {button:?} mouse button"),
+                EventType::ButtonRelease(button) => {
+                    write!(f, "Release {button:?} mouse button")
+                }
+                EventType::MouseMove { x, y } => write!(f, "Move mouse to ({x}, {y})"),
+                EventType::Wheel { delta_x, delta_y } => {
+                    write!(f, "Scroll by ({delta_x}, {delta_y})")
+                }
             },
+            global_event::EventKind::MouseMoveInWindow {
+                x,
+                y,
+                window_x,
+                window_y,
+            } => write!(
+                f,
+                "Move mouse to ({x}, {y}), ({window_x}, {window_y}) in the window"
+            ),
+            global_event::EventKind::TypeText(text) => write!(f, "Type {text:?}"),
+            global_event::EventKind::Chord { modifiers, key } => write!(
+                f,
+                "Press {}",
+                Chord {
+                    modifiers: *modifiers,
+                    key: *key
+                }
+            ),
             global_event::EventKind::FocusChange { window_title, .. } => {
                 write!(f, "Window changed to \"{window_title}\"")
             }
@@ -103,18 +153,143 @@ impl ItemSelectionState {
     }
 }
 
+/// Item edited in place, written as a line of the text macro format
+#[derive(Debug)]
+struct ItemEditor {
+    index: usize,
+    input: String,
+    /// Why `input` can't be committed
+    error: Option<String>,
+}
+
+impl ItemEditor {
+    fn new(index: usize, event: &PrintableEvent) -> Self {
+        Self {
+            index,
+            input: macro_file::text::print_event(&event.0.kind),
+            error: None,
+        }
+    }
+
+    fn parse(&self) -> Result<global_event::EventKind, String> {
+        macro_file::text::parse_event(&self.input)
+            .map_err(|e| format!("Column {}: {}", e.column, e.message))
+    }
+
+    /// Puts the key of `chord` in the edited key press or release, anything else
+    /// becomes the whole chord
+    fn fill_key(&mut self, chord: Chord) {
+        let kind = match self.parse() {
+            Ok(global_event::EventKind::Input(Input(EventType::KeyPress(_)))) => {
+                global_event::EventKind::Input(Input(EventType::KeyPress(chord.key)))
+            }
+            Ok(global_event::EventKind::Input(Input(EventType::KeyRelease(_)))) => {
+                global_event::EventKind::Input(Input(EventType::KeyRelease(chord.key)))
+            }
+            _ => global_event::EventKind::Chord {
+                modifiers: chord.modifiers,
+                key: chord.key,
+            },
+        };
+        self.input = macro_file::text::print_event(&kind);
+        self.error = None;
+    }
+}
+
+/// Events the "Add event" palette inserts, to be filled in afterwards
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventTemplate {
+    Delay,
+    KeyPress,
+    KeyRelease,
+    Chord,
+    FocusChange,
+    TypeText,
+    YieldFocus,
+}
+
+impl EventTemplate {
+    pub const ALL: [EventTemplate; 7] = [
+        EventTemplate::Delay,
+        EventTemplate::KeyPress,
+        EventTemplate::KeyRelease,
+        EventTemplate::Chord,
+        EventTemplate::FocusChange,
+        EventTemplate::TypeText,
+        EventTemplate::YieldFocus,
+    ];
+
+    fn kind(self) -> global_event::EventKind {
+        let key = rdev::Key::KeyA;
+        match self {
+            EventTemplate::Delay => global_event::EventKind::Delay(Duration::from_millis(100)),
+            EventTemplate::KeyPress => {
+                global_event::EventKind::Input(Input(EventType::KeyPress(key)))
+            }
+            EventTemplate::KeyRelease => {
+                global_event::EventKind::Input(Input(EventType::KeyRelease(key)))
+            }
+            EventTemplate::Chord => global_event::EventKind::Chord {
+                modifiers: chord::Modifiers {
+                    control: true,
+                    ..Default::default()
+                },
+                key,
+            },
+            EventTemplate::FocusChange => global_event::EventKind::FocusChange {
+                window_title: String::new(),
+            },
+            EventTemplate::TypeText => global_event::EventKind::TypeText(String::new()),
+            EventTemplate::YieldFocus => global_event::EventKind::YieldFocus,
+        }
+    }
+
+    /// Whether the inserted event waits for a key to be pressed to fill it in
+    fn captures_key(self) -> bool {
+        matches!(
+            self,
+            EventTemplate::KeyPress | EventTemplate::KeyRelease | EventTemplate::Chord
+        )
+    }
+}
+
+impl Display for EventTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EventTemplate::Delay => write!(f, "Delay"),
+            EventTemplate::KeyPress => write!(f, "Key press"),
+            EventTemplate::KeyRelease => write!(f, "Key release"),
+            EventTemplate::Chord => write!(f, "Chord"),
+            EventTemplate::FocusChange => write!(f, "Window focus"),
+            EventTemplate::TypeText => write!(f, "Text"),
+            EventTemplate::YieldFocus => write!(f, "Yield"),
+        }
+    }
+}
+
 pub struct State {
     global_event_listener_command_sender: Option<Sender<global_event::listener::Command>>,
     global_event_player_command_sender: Option<Sender<global_event::player::Command>>,
     current_listener_mode: global_event::listener::Mode,
     playback_mode: PlaybackMode,
-    items: Vec<PrintableEvent>,
-    selected_items_state: ItemSelectionState,
-    item_list_scroll_viewport: Option<Viewport>,
-    item_list_scroll_id: iced::widget::scrollable::Id,
+    playback_options: PlaybackOptions,
+    record_mouse: bool,
+    recording_mode: RecordingMode,
+    /// Item index of each event sent to the player
+    playback_item_indices: Vec<usize>,
+    /// Current iteration of the playback and how many there will be, if known
+    iteration: Option<(u32, Option<u32>)>,
+    library: Library,
+    macro_name_input: Option<String>,
+    item_editor: Option<ItemEditor>,
+    /// Last item clicked and when, to recognize double clicks
+    last_item_click: Option<(usize, Instant)>,
+    /// Item under the pointer while the selection is dragged
+    item_drag: Option<usize>,
     window_id: Option<iced::window::Id>,
     always_on_top: bool,
     modifiers: Modifiers,
+    exit_after_save: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -125,34 +300,84 @@ pub enum GlobalEventTrigger {
 
     PlayerReady(Sender<global_event::player::Command>),
     PlayerPlaybackJustStarted,
+    PlayerPlaybackJustPaused,
+    PlayerPlaybackJustResumed,
+    PlayerIterationJustStarted { iteration: u32, total: Option<u32> },
     PlayerPlaybackJustEnded,
     PlayerJustPlayed(usize),
+    PlayerBreakpointHit(usize),
 
     Event(global_event::Event),
+    HotkeyPressed(Chord),
+    PanicHotkeyPressed,
+    KeyCaptured(Chord),
 }
 
 #[derive(Debug, Clone)]
 pub enum Command {
-    StartRecording,
-    StartPlayback,
+    StartRecording(RecordingMode),
+    StartPlayback(PlaybackRange),
+    PausePlayback,
+    ResumePlayback,
+    StepPlayback,
     Stop,
     SetAlwaysOnTop(bool),
+    SetRecordMouse(bool),
+    SetRecordingMode(RecordingMode),
+    SetPlaybackOptions(PlaybackOptions),
     TriggerWindowId,
     SetWindowId(iced::window::Id),
     UpdateModifiers(Modifiers),
-    AddYieldEventAfterSelected,
+    AddEventAfterSelected(EventTemplate),
     ItemList(ListCommand),
+    Open,
+    OpenFrom(PathBuf),
+    Save,
+    SaveAs,
+    SaveTo(PathBuf),
+    SaveAll,
+    Exit,
+    Library(LibraryCommand),
 }
 
 #[derive(Debug, Clone)]
 pub enum Trigger {
     RecordButton,
     PlayButton,
+    PlayFromCursorButton,
+    PlaySelectionButton,
+    PauseButton,
+    ResumeButton,
+    StepButton,
     StopButton,
-    AddYieldButton,
+    ToggleBreakpointButton,
+    CollapseIntoTextButton,
+    ConvertIntoChordButton,
+    UndoButton,
+    RedoButton,
+    AddEventPickList(EventTemplate),
+    OpenButton,
+    SaveButton,
+    SaveAsButton,
     AlwaysOnTopCheckbox(bool),
+    RecordMouseCheckbox(bool),
+    RecordingModePickList(RecordingMode),
+    TimingPickList(Timing),
+    SpeedSlider(f64),
+    FixedDelaySlider(u32),
+    RepeatPickList(Repeat),
+    RepeatCountInput(String),
+    RepeatDurationInput(String),
+    IterationDelayInput(String),
     WindowId(iced::window::Id),
     GlobalEvent(GlobalEventTrigger),
+    OpenFileChosen(Option<PathBuf>),
+    SaveFileChosen(Option<PathBuf>),
+    CloseRequested,
+    UnsavedChangesDialogClosed(MessageDialogResult),
+    DeleteMacroButton,
+    DeleteMacroDialogClosed(MessageDialogResult),
+    EscapeKey,
 }
 
 #[derive(Debug, Clone)]
@@ -161,9 +386,45 @@ pub enum ListCommand {
     SelectNext,
     SelectPrevious,
     DeleteItem,
+    ToggleBreakpoint,
+    CollapseIntoText,
+    ConvertIntoChord,
+    Undo,
+    Redo,
+    StartEditingItem(usize),
+    EditItemInput(String),
+    CommitItemEdit,
+    CancelItemEdit,
+    /// Fills the edited item with the next key pressed
+    CaptureKey,
+    Copy,
+    Cut,
+    Paste,
+    PasteContent(Option<String>),
+    MoveSelectionUp,
+    MoveSelectionDown,
+    DragOver(usize),
+    /// The dragged selection is dropped on an item
+    Drop(usize),
+    /// The mouse button is released away from the items
+    EndDrag,
     SetScrollableViewport(Viewport),
 }
 
+#[derive(Debug, Clone)]
+pub enum LibraryCommand {
+    Select(usize),
+    New,
+    Duplicate,
+    Delete,
+    StartRenaming,
+    EditName(String),
+    Rename,
+    CancelRenaming,
+    EditHotkey(String),
+    BindHotkey,
+}
+
 #[derive(Debug, Clone)]
 pub enum Message {
     Command(Command),
@@ -172,25 +433,26 @@ pub enum Message {
 
 impl State {
     pub fn new() -> (State, Task<Message>) {
-        let items = std::fs::read_to_string("macro.json")
-            .map_err(|e| e.to_string())
-            .and_then(|content| {
-                serde_json::from_str::<Vec<PrintableEvent>>(&content).map_err(|e| e.to_string())
-            })
-            .unwrap_or_default();
         let always_on_top = true;
         let state = State {
             global_event_listener_command_sender: Default::default(),
             global_event_player_command_sender: Default::default(),
             playback_mode: Default::default(),
+            playback_options: Default::default(),
+            record_mouse: false,
+            recording_mode: Default::default(),
+            playback_item_indices: Vec::new(),
+            iteration: None,
             current_listener_mode: Default::default(),
-            items,
-            selected_items_state: Default::default(),
-            item_list_scroll_viewport: Default::default(),
-            item_list_scroll_id: iced::widget::scrollable::Id::unique(),
+            library: Library::load(Library::default_directory()),
+            macro_name_input: None,
+            item_editor: None,
+            last_item_click: None,
+            item_drag: None,
             window_id: None,
             always_on_top,
             modifiers: Modifiers::default(),
+            exit_after_save: false,
         };
         (
             state,
@@ -198,20 +460,29 @@ impl State {
         )
     }
 
-    pub fn title(_state: &State) -> String {
-        "Powerkey".into()
+    pub fn title(state: &State) -> String {
+        let current = state.library.current();
+        format!(
+            "Powerkey - {}{}",
+            current.name(),
+            if current.dirty { "*" } else { "" }
+        )
     }
 
     fn scroll_to_item_task(&self) -> Task<Message> {
-        if let Some(viewport) = self.item_list_scroll_viewport {
-            debug_assert_eq!(1, self.selected_items_state.selected_indices.len());
-            let Some(selected_item_index) =
-                self.selected_items_state.selected_indices.first().cloned()
+        let current = self.library.current();
+        if let Some(viewport) = current.item_list_scroll_viewport {
+            debug_assert_eq!(1, current.selected_items_state.selected_indices.len());
+            let Some(selected_item_index) = current
+                .selected_items_state
+                .selected_indices
+                .first()
+                .cloned()
             else {
                 return Task::none();
             };
 
-            let item_height = viewport.content_bounds().height / self.items.len() as f32;
+            let item_height = viewport.content_bounds().height / current.items.len() as f32;
             let top = viewport.absolute_offset().y;
             let bottom = viewport.absolute_offset().y + viewport.bounds().height;
             let item_top = item_height * selected_item_index as f32;
@@ -227,7 +498,7 @@ impl State {
 
             if y_scroll != 0.0 {
                 return iced::widget::scrollable::scroll_by(
-                    self.item_list_scroll_id.clone(),
+                    current.item_list_scroll_id.clone(),
                     AbsoluteOffset {
                         x: 0.0,
                         y: y_scroll,
@@ -242,34 +513,64 @@ impl State {
 
     fn handle_command(&mut self, command: Command) -> Task<Message> {
         match command {
-            Command::StartRecording => {
+            Command::StartRecording(mode) => {
                 self.playback_mode = PlaybackMode::Record;
-                self.items.clear();
+                self.item_editor = None;
+                self.library
+                    .current_mut()
+                    .start_recording(mode, SystemTime::now());
                 self.global_event_listener_command_sender
                     .try_send(global_event::listener::Command::ChangeMode(
-                        global_event::listener::Mode::Listen,
+                        global_event::listener::Mode::Listen {
+                            record_mouse: self.record_mouse,
+                        },
                     ))
                     .unwrap();
             }
-            Command::StartPlayback => {
+            Command::StartPlayback(range) => {
                 if let Some(listener_command_sender) =
                     self.global_event_listener_command_sender.as_ref().cloned()
                 {
+                    let current = self.library.current();
+                    self.playback_item_indices = current.playback_indices(range);
                     self.global_event_player_command_sender
                         .try_send(global_event::player::Command::InitializePlayback(
-                            self.items
-                                .clone()
-                                .into_iter()
-                                .map(|event| event.0)
+                            self.playback_item_indices
+                                .iter()
+                                .map(|index| current.items[*index].0.clone())
                                 .collect_vec(),
+                            self.playback_options,
+                            current.playback_breakpoints(&self.playback_item_indices),
                             listener_command_sender,
                         ))
                         .unwrap();
                     self.playback_mode = PlaybackMode::PlayerWaitsForGrab;
                 }
             }
+            Command::PausePlayback => {
+                if let PlaybackMode::Play = self.playback_mode {
+                    self.global_event_player_command_sender
+                        .try_send(global_event::player::Command::PausePlayback)
+                        .unwrap();
+                }
+            }
+            Command::ResumePlayback => {
+                if let PlaybackMode::Paused = self.playback_mode {
+                    self.global_event_player_command_sender
+                        .try_send(global_event::player::Command::ResumePlayback)
+                        .unwrap();
+                    self.playback_mode = PlaybackMode::PlayerWaitsForGrab;
+                }
+            }
+            Command::StepPlayback => {
+                if let PlaybackMode::Paused = self.playback_mode {
+                    self.global_event_player_command_sender
+                        .try_send(global_event::player::Command::StepPlayback)
+                        .unwrap();
+                    self.playback_mode = PlaybackMode::PlayerWaitsForGrab;
+                }
+            }
             Command::Stop => {
-                if let PlaybackMode::Play = &self.playback_mode {}
                 if !matches!(
                     self.current_listener_mode,
                     global_event::listener::Mode::Disabled
@@ -287,9 +588,16 @@ impl State {
                         .unwrap();
                 }
 
+                // While listening, recordings end once the held back pointer move is sent
+                if !matches!(
+                    self.current_listener_mode,
+                    global_event::listener::Mode::Listen { .. }
+                ) {
+                    self.library.finish_recording();
+                }
                 self.playback_mode = PlaybackMode::Idle;
-
-                // std::fs::write("macro.json", serde_json::to_string(&self.items).unwrap()).unwrap();
+                self.iteration = None;
+                self.item_editor = None;
             }
             Command::SetAlwaysOnTop(always_on_top) => {
                 if let Some(window_id) = self.window_id {
@@ -308,72 +616,469 @@ impl State {
                     );
                 }
             }
+            Command::SetRecordMouse(record_mouse) => self.record_mouse = record_mouse,
+            Command::SetRecordingMode(mode) => self.recording_mode = mode,
+            Command::SetPlaybackOptions(options) => self.playback_options = options,
             Command::TriggerWindowId => {
                 return iced::window::get_oldest()
                     .and_then(|id| Task::done(Message::Trigger(Trigger::WindowId(id))));
             }
             Command::UpdateModifiers(modifiers) => self.modifiers = modifiers,
-            Command::AddYieldEventAfterSelected => {
-                let yield_event = PrintableEvent(global_event::Event::new(
-                    SystemTime::now(),
-                    global_event::EventKind::YieldFocus,
-                ));
-                if let Some(last_selected_index) = self.selected_items_state.get_last_selected() {
-                    self.items.insert(last_selected_index + 1, yield_event);
-                } else {
-                    self.items.push(yield_event);
+            Command::AddEventAfterSelected(template) => {
+                if !matches!(self.playback_mode, PlaybackMode::Idle) {
+                    return Task::none();
+                }
+                self.close_item_editor();
+                let event =
+                    PrintableEvent(global_event::Event::new(SystemTime::now(), template.kind()));
+                let current = self.library.current_mut();
+                let index = current
+                    .selected_items_state
+                    .get_last_selected()
+                    .map_or(current.items.len(), |last_selected_index| {
+                        last_selected_index + 1
+                    });
+                current.insert_items(index, vec![event]);
+                current.selected_items_state.select(index);
+                let scroll = self.scroll_to_item_task();
+                if let EventTemplate::YieldFocus = template {
+                    return scroll;
                 }
+                let list_command =
+                    |command| Task::done(Message::Command(Command::ItemList(command)));
+                let mut edit = list_command(ListCommand::StartEditingItem(index));
+                if template.captures_key() {
+                    edit = edit.chain(list_command(ListCommand::CaptureKey));
+                }
+                return Task::batch([scroll, edit]);
             }
             Command::SetWindowId(id) => self.window_id = Some(id),
             Command::ItemList(command) => return self.handle_list_command(command),
+            Command::Open => {
+                return Task::perform(
+                    AsyncFileDialog::new()
+                        .add_filter(macro_file::FILTER_NAME, &[macro_file::EXTENSION])
+                        .add_filter(macro_file::TEXT_FILTER_NAME, &[macro_file::TEXT_EXTENSION])
+                        .pick_file(),
+                    |file| {
+                        Message::Trigger(Trigger::OpenFileChosen(
+                            file.map(|file| file.path().to_path_buf()),
+                        ))
+                    },
+                );
+            }
+            Command::OpenFrom(path) => {
+                if let Some(index) = self.library.position(&path) {
+                    return Task::done(Message::Command(Command::Library(LibraryCommand::Select(
+                        index,
+                    ))));
+                }
+                match macro_file::load(&path) {
+                    Ok(document) => {
+                        self.library.open(path, document);
+                        self.macro_name_input = None;
+                        self.send_hotkeys();
+                        return Task::done(Message::Command(Command::Stop))
+                            .chain(self.restore_scroll_task());
+                    }
+                    Err(e) => error!("Could not open {}: {e}", path.display()),
+                }
+            }
+            Command::Save => {
+                return Task::done(Message::Command(Command::SaveTo(
+                    self.library.current().file_path.clone(),
+                )));
+            }
+            Command::SaveAs => {
+                let current = self.library.current();
+                let extension = current
+                    .file_path
+                    .extension()
+                    .map(|extension| extension.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| macro_file::EXTENSION.to_owned());
+                return Task::perform(
+                    AsyncFileDialog::new()
+                        .add_filter(macro_file::FILTER_NAME, &[macro_file::EXTENSION])
+                        .add_filter(macro_file::TEXT_FILTER_NAME, &[macro_file::TEXT_EXTENSION])
+                        .set_directory(&self.library.directory)
+                        .set_file_name(format!("{}.{extension}", current.name()))
+                        .save_file(),
+                    |file| {
+                        Message::Trigger(Trigger::SaveFileChosen(
+                            file.map(|file| file.path().to_path_buf()),
+                        ))
+                    },
+                );
+            }
+            Command::SaveTo(path) => {
+                if let Err(e) = save_macro(self.library.current_mut(), path) {
+                    error!("{e}");
+                }
+            }
+            Command::SaveAll => {
+                let mut all_saved = true;
+                for macro_ in self.library.macros.iter_mut().filter(|macro_| macro_.dirty) {
+                    if let Err(e) = save_macro(macro_, macro_.file_path.clone()) {
+                        error!("{e}");
+                        all_saved = false;
+                    }
+                }
+                if std::mem::take(&mut self.exit_after_save) && all_saved {
+                    return Task::done(Message::Command(Command::Exit));
+                }
+            }
+            Command::Exit => return iced::exit(),
+            Command::Library(command) => return self.handle_library_command(command),
+        }
+        Task::none()
+    }
+
+    /// Scrolls the item list back to where it was the last time the current macro was shown
+    fn restore_scroll_task(&self) -> Task<Message> {
+        let current = self.library.current();
+        iced::widget::scrollable::scroll_to(
+            current.item_list_scroll_id.clone(),
+            current
+                .item_list_scroll_viewport
+                .map(|viewport| viewport.absolute_offset())
+                .unwrap_or_default(),
+        )
+    }
+
+    fn handle_library_command(&mut self, command: LibraryCommand) -> Task<Message> {
+        match command {
+            LibraryCommand::Select(index) => {
+                if index != self.library.current && index < self.library.macros.len() {
+                    self.library.current = index;
+                    self.macro_name_input = None;
+                    return Task::done(Message::Command(Command::Stop))
+                        .chain(self.restore_scroll_task());
+                }
+            }
+            LibraryCommand::New => {
+                self.library.create("Untitled", Vec::new());
+                self.macro_name_input = None;
+                return Task::done(Message::Command(Command::Stop));
+            }
+            LibraryCommand::Duplicate => {
+                self.library.duplicate_current();
+                self.macro_name_input = None;
+                return Task::done(Message::Command(Command::Stop));
+            }
+            LibraryCommand::Delete => {
+                self.library.delete_current();
+                self.macro_name_input = None;
+                self.send_hotkeys();
+                return Task::done(Message::Command(Command::Stop))
+                    .chain(self.restore_scroll_task());
+            }
+            LibraryCommand::StartRenaming => {
+                self.macro_name_input = Some(self.library.current().name());
+                return text_input::focus(MACRO_NAME_INPUT_ID);
+            }
+            LibraryCommand::EditName(name) => {
+                if self.macro_name_input.is_some() {
+                    self.macro_name_input = Some(name);
+                }
+            }
+            LibraryCommand::Rename => {
+                if let Some(name) = &self.macro_name_input {
+                    match self.library.rename_current(name) {
+                        Ok(()) => self.macro_name_input = None,
+                        Err(e) => error!("{e}"),
+                    }
+                }
+            }
+            LibraryCommand::CancelRenaming => self.macro_name_input = None,
+            LibraryCommand::EditHotkey(hotkey) => {
+                self.library.current_mut().hotkey_input = hotkey;
+            }
+            LibraryCommand::BindHotkey => {
+                let hotkey_input = self.library.current().hotkey_input.trim().to_owned();
+                let hotkey = if hotkey_input.is_empty() {
+                    None
+                } else {
+                    match hotkey_input.parse::<Chord>() {
+                        Ok(hotkey) => Some(hotkey),
+                        Err(e) => {
+                            error!("{e}");
+                            return Task::none();
+                        }
+                    }
+                };
+                if let Some(hotkey) = hotkey
+                    && let Some(index) = self.library.find_by_hotkey(hotkey)
+                    && index != self.library.current
+                {
+                    error!(
+                        "Hotkey {hotkey} is already bound to \"{}\"",
+                        self.library.macros[index].name()
+                    );
+                    return Task::none();
+                }
+                self.library.current_mut().set_hotkey(hotkey);
+                self.send_hotkeys();
+            }
         }
         Task::none()
     }
 
+    fn send_hotkeys(&mut self) {
+        if self.global_event_listener_command_sender.is_some() {
+            self.global_event_listener_command_sender
+                .try_send(global_event::listener::Command::SetHotkeys(
+                    self.library.hotkeys(),
+                ))
+                .unwrap();
+        }
+    }
+
+    /// Puts the selected items on the clipboard as text macro lines
+    fn copy_selection(&self) -> Task<Message> {
+        let current = self.library.current();
+        let events = current
+            .selected_items_state
+            .iter()
+            .filter_map(|index| current.items.get(index))
+            .map(|item| item.0.clone())
+            .collect_vec();
+        if events.is_empty() {
+            return Task::none();
+        }
+        iced::clipboard::write(macro_file::text::print(&events))
+    }
+
+    /// Closes the item editor, giving up on the key it may be waiting for
+    fn close_item_editor(&mut self) {
+        if self.item_editor.take().is_some()
+            && let global_event::listener::Mode::CaptureKey = self.current_listener_mode
+        {
+            self.global_event_listener_command_sender
+                .try_send(global_event::listener::Command::ChangeMode(
+                    global_event::listener::Mode::Disabled,
+                ))
+                .unwrap();
+        }
+    }
+
     fn handle_list_command(&mut self, command: ListCommand) -> Task<Message> {
+        // Anything else changes the list or the selection under the item being edited
+        if !matches!(
+            command,
+            ListCommand::StartEditingItem(_)
+                | ListCommand::EditItemInput(_)
+                | ListCommand::CommitItemEdit
+                | ListCommand::CaptureKey
+                | ListCommand::DragOver(_)
+                | ListCommand::Drop(_)
+                | ListCommand::EndDrag
+                | ListCommand::SetScrollableViewport(_)
+        ) {
+            self.close_item_editor();
+        }
         match command {
             ListCommand::SelectItem(index) => {
-                if self.modifiers.control() {
-                    self.selected_items_state.add_item_to_selection(index);
-                } else if self.modifiers.shift() {
-                    self.selected_items_state.expand_to(index);
+                let now = Instant::now();
+                let double_clicked = self.last_item_click.replace((index, now)).is_some_and(
+                    |(last_index, last_time)| {
+                        last_index == index && now - last_time <= DOUBLE_CLICK_INTERVAL
+                    },
+                );
+                self.item_drag = Some(index);
+                let modifiers = self.modifiers;
+                let current = self.library.current_mut();
+                if modifiers.control() {
+                    current.selected_items_state.add_item_to_selection(index);
+                } else if modifiers.shift() {
+                    current.selected_items_state.expand_to(index);
                 } else {
-                    self.selected_items_state.select(index);
+                    current.selected_items_state.select(index);
+                    if double_clicked {
+                        self.last_item_click = None;
+                        return Task::done(Message::Command(Command::ItemList(
+                            ListCommand::StartEditingItem(index),
+                        )));
+                    }
                 }
             }
             ListCommand::SelectNext => {
-                if let Some(last_item_selected) = self.selected_items_state.get_last_selected() {
+                let current = self.library.current_mut();
+                if let Some(last_item_selected) = current.selected_items_state.get_last_selected() {
                     let next_index = last_item_selected + 1;
-                    let next_index = next_index.clamp(0, self.items.len() - 1);
-                    self.selected_items_state.select(next_index);
+                    let next_index = next_index.clamp(0, current.items.len() - 1);
+                    current.selected_items_state.select(next_index);
                     return self.scroll_to_item_task();
                 }
             }
             ListCommand::SelectPrevious => {
-                if let Some(last_item_selected) = self.selected_items_state.get_first_selected() {
+                let current = self.library.current_mut();
+                if let Some(last_item_selected) = current.selected_items_state.get_first_selected()
+                {
                     let next_index = last_item_selected.saturating_sub(1);
-                    self.selected_items_state.select(next_index);
+                    current.selected_items_state.select(next_index);
                     return self.scroll_to_item_task();
                 }
             }
             ListCommand::DeleteItem => {
-                if let Some(first_item_selected) = self.selected_items_state.get_first_selected() {
-                    for (index_index, index_to_delete) in
-                        self.selected_items_state.iter().enumerate()
-                    {
-                        self.items.remove(index_to_delete - index_index);
-                    }
-                    if self.items.is_empty() {
-                        self.selected_items_state.unselect()
+                let current = self.library.current_mut();
+                if let PlaybackMode::Idle = self.playback_mode
+                    && let Some(first_item_selected) =
+                        current.selected_items_state.get_first_selected()
+                {
+                    current.remove_items(&current.selected_items_state.selected_indices.clone());
+                    if current.items.is_empty() {
+                        current.selected_items_state.unselect()
                     } else {
-                        self.selected_items_state
-                            .select(first_item_selected.clamp(0, self.items.len() - 1));
+                        current
+                            .selected_items_state
+                            .select(first_item_selected.clamp(0, current.items.len() - 1));
                     }
                     return Task::done(Message::Command(Command::Stop));
                 }
             }
+            ListCommand::ToggleBreakpoint => self.library.current_mut().toggle_breakpoints(),
+            ListCommand::CollapseIntoText => {
+                if let PlaybackMode::Idle = self.playback_mode
+                    && let Err(e) = self.library.current_mut().collapse_selection_into_text()
+                {
+                    error!("{e}");
+                }
+            }
+            ListCommand::ConvertIntoChord => {
+                if let PlaybackMode::Idle = self.playback_mode
+                    && let Err(e) = self.library.current_mut().convert_selection_into_chord()
+                {
+                    error!("{e}");
+                }
+            }
+            ListCommand::Undo => {
+                if let PlaybackMode::Idle = self.playback_mode {
+                    self.library.current_mut().undo();
+                }
+            }
+            ListCommand::Redo => {
+                if let PlaybackMode::Idle = self.playback_mode {
+                    self.library.current_mut().redo();
+                }
+            }
+            ListCommand::StartEditingItem(index) => {
+                if let PlaybackMode::Idle = self.playback_mode
+                    && let Some(event) = self.library.current().items.get(index)
+                {
+                    self.item_editor = Some(ItemEditor::new(index, event));
+                    return text_input::focus(ITEM_INPUT_ID);
+                }
+            }
+            ListCommand::EditItemInput(input) => {
+                if let Some(editor) = &mut self.item_editor {
+                    editor.input = input;
+                    editor.error = editor.parse().err();
+                }
+            }
+            ListCommand::CommitItemEdit => {
+                if let Some(editor) = &self.item_editor {
+                    match editor.parse() {
+                        Ok(kind) => {
+                            self.library.current_mut().edit_item(editor.index, kind);
+                            self.close_item_editor();
+                        }
+                        Err(e) => error!("{e}"),
+                    }
+                }
+            }
+            ListCommand::CancelItemEdit => {}
+            ListCommand::CaptureKey => {
+                if self.item_editor.is_some()
+                    && let PlaybackMode::Idle = self.playback_mode
+                {
+                    self.global_event_listener_command_sender
+                        .try_send(global_event::listener::Command::ChangeMode(
+                            global_event::listener::Mode::CaptureKey,
+                        ))
+                        .unwrap();
+                }
+            }
+            ListCommand::MoveSelectionUp => {
+                let current = self.library.current_mut();
+                if let PlaybackMode::Idle = self.playback_mode
+                    && let Some(first_selected) = current.selected_items_state.get_first_selected()
+                    && first_selected > 0
+                {
+                    current.move_selection_onto(first_selected - 1);
+                }
+            }
+            ListCommand::MoveSelectionDown => {
+                let current = self.library.current_mut();
+                if let PlaybackMode::Idle = self.playback_mode
+                    && let Some(last_selected) = current.selected_items_state.get_last_selected()
+                {
+                    current.move_selection_onto(last_selected + 1);
+                }
+            }
+            ListCommand::DragOver(index) => {
+                if self.item_drag.is_some() {
+                    self.item_drag = Some(index);
+                }
+            }
+            ListCommand::Drop(index) => {
+                if self.item_drag.take().is_some()
+                    && let PlaybackMode::Idle = self.playback_mode
+                    && !self
+                        .library
+                        .current()
+                        .selected_items_state
+                        .is_selected(index)
+                {
+                    self.close_item_editor();
+                    self.library.current_mut().move_selection_onto(index);
+                }
+            }
+            ListCommand::EndDrag => self.item_drag = None,
+            ListCommand::Copy => return self.copy_selection(),
+            ListCommand::Cut => {
+                if let PlaybackMode::Idle = self.playback_mode {
+                    return self.copy_selection().chain(Task::done(Message::Command(
+                        Command::ItemList(ListCommand::DeleteItem),
+                    )));
+                }
+            }
+            ListCommand::Paste => {
+                if let PlaybackMode::Idle = self.playback_mode {
+                    return iced::clipboard::read().map(|content| {
+                        Message::Command(Command::ItemList(ListCommand::PasteContent(content)))
+                    });
+                }
+            }
+            ListCommand::PasteContent(content) => {
+                // Playback or recording may have started while the clipboard was read
+                let (PlaybackMode::Idle, Some(content)) = (&self.playback_mode, content) else {
+                    return Task::none();
+                };
+                let events = match macro_file::parse_events(&content, SystemTime::now()) {
+                    Ok(events) => events,
+                    Err(e) => {
+                        error!("Could not paste: {e}");
+                        return Task::none();
+                    }
+                };
+                let current = self.library.current_mut();
+                let index = current
+                    .selected_items_state
+                    .get_last_selected()
+                    .map_or(current.items.len(), |last_selected_index| {
+                        last_selected_index + 1
+                    });
+                let count = events.len();
+                current.insert_items(index, events.into_iter().map(PrintableEvent).collect());
+                current.selected_items_state.unselect();
+                for pasted_index in index..index + count {
+                    current
+                        .selected_items_state
+                        .add_item_to_selection(pasted_index);
+                }
+            }
             ListCommand::SetScrollableViewport(viewport) => {
-                self.item_list_scroll_viewport = Some(viewport);
+                self.library.current_mut().item_list_scroll_viewport = Some(viewport);
             }
         }
         Task::none()
@@ -381,19 +1086,170 @@ impl State {
 
     fn handle_trigger(&mut self, trigger: Trigger) -> Task<Message> {
         match trigger {
-            Trigger::RecordButton => Task::done(Message::Command(Command::StartRecording)),
-            Trigger::PlayButton => Task::done(Message::Command(Command::StartPlayback)),
+            Trigger::RecordButton => Task::done(Message::Command(Command::StartRecording(
+                self.recording_mode,
+            ))),
+            Trigger::RecordingModePickList(mode) => {
+                Task::done(Message::Command(Command::SetRecordingMode(mode)))
+            }
+            Trigger::PlayButton => {
+                Task::done(Message::Command(Command::StartPlayback(PlaybackRange::All)))
+            }
+            Trigger::PlayFromCursorButton => Task::done(Message::Command(Command::StartPlayback(
+                PlaybackRange::FromCursor,
+            ))),
+            Trigger::PlaySelectionButton => Task::done(Message::Command(Command::StartPlayback(
+                PlaybackRange::Selection,
+            ))),
+            Trigger::PauseButton => Task::done(Message::Command(Command::PausePlayback)),
+            Trigger::ResumeButton => Task::done(Message::Command(Command::ResumePlayback)),
+            Trigger::StepButton => Task::done(Message::Command(Command::StepPlayback)),
             Trigger::StopButton => Task::done(Message::Command(Command::Stop)),
+            Trigger::EscapeKey => Task::batch([
+                Task::done(Message::Command(Command::Library(
+                    LibraryCommand::CancelRenaming,
+                ))),
+                Task::done(Message::Command(Command::ItemList(
+                    ListCommand::CancelItemEdit,
+                ))),
+            ]),
+            Trigger::ToggleBreakpointButton => Task::done(Message::Command(Command::ItemList(
+                ListCommand::ToggleBreakpoint,
+            ))),
+            Trigger::CollapseIntoTextButton => Task::done(Message::Command(Command::ItemList(
+                ListCommand::CollapseIntoText,
+            ))),
+            Trigger::ConvertIntoChordButton => Task::done(Message::Command(Command::ItemList(
+                ListCommand::ConvertIntoChord,
+            ))),
+            Trigger::UndoButton => {
+                Task::done(Message::Command(Command::ItemList(ListCommand::Undo)))
+            }
+            Trigger::RedoButton => {
+                Task::done(Message::Command(Command::ItemList(ListCommand::Redo)))
+            }
             Trigger::AlwaysOnTopCheckbox(checked) => {
                 Task::done(Message::Command(Command::SetAlwaysOnTop(checked)))
             }
+            Trigger::RecordMouseCheckbox(checked) => {
+                Task::done(Message::Command(Command::SetRecordMouse(checked)))
+            }
+            Trigger::TimingPickList(timing) => Task::done(Message::Command(
+                Command::SetPlaybackOptions(PlaybackOptions {
+                    timing,
+                    ..self.playback_options
+                }),
+            )),
+            Trigger::SpeedSlider(speed) => Task::done(Message::Command(
+                Command::SetPlaybackOptions(PlaybackOptions {
+                    speed,
+                    ..self.playback_options
+                }),
+            )),
+            Trigger::FixedDelaySlider(milliseconds) => Task::done(Message::Command(
+                Command::SetPlaybackOptions(PlaybackOptions {
+                    fixed_delay: Duration::from_millis(milliseconds.into()),
+                    ..self.playback_options
+                }),
+            )),
+            Trigger::RepeatPickList(repeat) => Task::done(Message::Command(
+                Command::SetPlaybackOptions(PlaybackOptions {
+                    repeat,
+                    ..self.playback_options
+                }),
+            )),
+            Trigger::RepeatCountInput(input) => match parse_number_input(&input) {
+                Some(repeat_count) => Task::done(Message::Command(Command::SetPlaybackOptions(
+                    PlaybackOptions {
+                        repeat_count,
+                        ..self.playback_options
+                    },
+                ))),
+                None => Task::none(),
+            },
+            Trigger::RepeatDurationInput(input) => match parse_number_input(&input) {
+                Some(seconds) => Task::done(Message::Command(Command::SetPlaybackOptions(
+                    PlaybackOptions {
+                        repeat_duration: Duration::from_secs(seconds.into()),
+                        ..self.playback_options
+                    },
+                ))),
+                None => Task::none(),
+            },
+            Trigger::IterationDelayInput(input) => match parse_number_input(&input) {
+                Some(milliseconds) => Task::done(Message::Command(Command::SetPlaybackOptions(
+                    PlaybackOptions {
+                        iteration_delay: Duration::from_millis(milliseconds.into()),
+                        ..self.playback_options
+                    },
+                ))),
+                None => Task::none(),
+            },
             Trigger::WindowId(id) => Task::done(Message::Command(Command::SetWindowId(id))),
             Trigger::GlobalEvent(global_event_message) => {
                 self.handle_global_event_message(global_event_message)
             }
-            Trigger::AddYieldButton => {
-                Task::done(Message::Command(Command::AddYieldEventAfterSelected))
+            Trigger::AddEventPickList(template) => {
+                Task::done(Message::Command(Command::AddEventAfterSelected(template)))
+            }
+            Trigger::OpenButton => Task::done(Message::Command(Command::Open)),
+            Trigger::SaveButton => Task::done(Message::Command(Command::Save)),
+            Trigger::SaveAsButton => Task::done(Message::Command(Command::SaveAs)),
+            Trigger::OpenFileChosen(path) => match path {
+                Some(path) => Task::done(Message::Command(Command::OpenFrom(path))),
+                None => Task::none(),
+            },
+            Trigger::SaveFileChosen(path) => match path {
+                Some(path) => Task::done(Message::Command(Command::SaveTo(path))),
+                None => Task::none(),
+            },
+            Trigger::CloseRequested => {
+                let dirty_macro_names = self
+                    .library
+                    .dirty_macros()
+                    .map(|macro_| format!("\"{}\"", macro_.name()))
+                    .join(", ");
+                if dirty_macro_names.is_empty() {
+                    return Task::done(Message::Command(Command::Exit));
+                }
+                Task::perform(
+                    AsyncMessageDialog::new()
+                        .set_level(MessageLevel::Warning)
+                        .set_title("Unsaved changes")
+                        .set_description(format!(
+                            "Save changes to {dirty_macro_names} before closing?"
+                        ))
+                        .set_buttons(MessageButtons::YesNoCancel)
+                        .show(),
+                    |result| Message::Trigger(Trigger::UnsavedChangesDialogClosed(result)),
+                )
             }
+            Trigger::UnsavedChangesDialogClosed(result) => match result {
+                MessageDialogResult::Yes => {
+                    self.exit_after_save = true;
+                    Task::done(Message::Command(Command::SaveAll))
+                }
+                MessageDialogResult::No => Task::done(Message::Command(Command::Exit)),
+                _ => Task::none(),
+            },
+            Trigger::DeleteMacroButton => Task::perform(
+                AsyncMessageDialog::new()
+                    .set_level(MessageLevel::Warning)
+                    .set_title("Delete macro")
+                    .set_description(format!(
+                        "Delete \"{}\" from the library? Its file will be removed.",
+                        self.library.current().name()
+                    ))
+                    .set_buttons(MessageButtons::YesNo)
+                    .show(),
+                |result| Message::Trigger(Trigger::DeleteMacroDialogClosed(result)),
+            ),
+            Trigger::DeleteMacroDialogClosed(result) => match result {
+                MessageDialogResult::Yes => {
+                    Task::done(Message::Command(Command::Library(LibraryCommand::Delete)))
+                }
+                _ => Task::none(),
+            },
         }
     }
 
@@ -404,6 +1260,7 @@ impl State {
         match global_event_message {
             GlobalEventTrigger::ListenerReady(sender) => {
                 self.global_event_listener_command_sender = Some(sender);
+                self.send_hotkeys();
             }
             GlobalEventTrigger::ListenerModeJustChanged(mode) => {
                 if matches!(self.playback_mode, PlaybackMode::PlayerWaitsForGrab)
@@ -413,6 +1270,18 @@ impl State {
                         .try_send(global_event::player::Command::NotifyGrabReady)
                         .unwrap();
                 }
+                if matches!(mode, global_event::listener::Mode::Disabled)
+                    && self.global_event_player_command_sender.is_some()
+                {
+                    self.global_event_player_command_sender
+                        .try_send(global_event::player::Command::NotifyGrabReleased)
+                        .unwrap();
+                }
+                if !matches!(mode, global_event::listener::Mode::Listen { .. })
+                    && !matches!(self.playback_mode, PlaybackMode::Record)
+                {
+                    self.library.finish_recording();
+                }
                 self.current_listener_mode = mode;
             }
             GlobalEventTrigger::PlayerReady(sender) => {
@@ -422,11 +1291,60 @@ impl State {
                 return Task::done(Message::Command(Command::Stop));
             }
             GlobalEventTrigger::PlayerJustPlayed(index) => {
-                self.selected_items_state.select(index);
+                if let Some(item_index) = self.playback_item_indices.get(index).cloned() {
+                    self.library
+                        .current_mut()
+                        .selected_items_state
+                        .select(item_index);
+                }
+            }
+            GlobalEventTrigger::PlayerBreakpointHit(index) => {
+                self.playback_mode = PlaybackMode::Paused;
+                if let Some(item_index) = self.playback_item_indices.get(index).cloned() {
+                    self.library
+                        .current_mut()
+                        .selected_items_state
+                        .select(item_index);
+                    return self.scroll_to_item_task();
+                }
             }
             GlobalEventTrigger::Event(event) => self.handle_global_event(event),
+            GlobalEventTrigger::KeyCaptured(chord) => {
+                if let Some(editor) = &mut self.item_editor {
+                    editor.fill_key(chord);
+                }
+            }
+            GlobalEventTrigger::PanicHotkeyPressed => {
+                return Task::done(Message::Command(Command::Stop));
+            }
+            GlobalEventTrigger::HotkeyPressed(hotkey) => {
+                if !matches!(self.playback_mode, PlaybackMode::Idle) {
+                    return Task::none();
+                }
+                if let Some(index) = self.library.find_by_hotkey(hotkey) {
+                    self.close_item_editor();
+                    self.library.current = index;
+                    self.macro_name_input = None;
+                    return Task::done(Message::Command(Command::StartPlayback(
+                        PlaybackRange::All,
+                    )))
+                    .chain(self.restore_scroll_task());
+                }
+            }
             GlobalEventTrigger::PlayerPlaybackJustStarted => {
                 self.playback_mode = PlaybackMode::Play;
+                self.iteration = Some((1, self.playback_options.total_iterations()));
+            }
+            GlobalEventTrigger::PlayerPlaybackJustPaused => {
+                if let PlaybackMode::Play = self.playback_mode {
+                    self.playback_mode = PlaybackMode::Paused;
+                }
+            }
+            GlobalEventTrigger::PlayerPlaybackJustResumed => {
+                self.playback_mode = PlaybackMode::Play;
+            }
+            GlobalEventTrigger::PlayerIterationJustStarted { iteration, total } => {
+                self.iteration = Some((iteration, total));
             }
             GlobalEventTrigger::ListenerAddGrabIgnoreListDone => {
                 self.global_event_player_command_sender
@@ -439,16 +1357,14 @@ impl State {
 
     fn handle_global_event(&mut self, event: global_event::Event) {
         match (&self.current_listener_mode, &mut self.playback_mode) {
-            (global_event::listener::Mode::Listen, PlaybackMode::Record) => {
-                if let Some(previous_event) = self.items.last() {
-                    if let Ok(delay) = event.time.duration_since(previous_event.0.time) {
-                        self.items.push(PrintableEvent(global_event::Event::new(
-                            SystemTime::now(),
-                            global_event::EventKind::Delay(delay),
-                        )));
-                    }
+            // Still listening after a stop, for the last pointer move
+            (
+                global_event::listener::Mode::Listen { .. },
+                PlaybackMode::Record | PlaybackMode::Idle,
+            ) => {
+                if let Some(macro_) = self.library.recording_mut() {
+                    macro_.record(event);
                 }
-                self.items.push(PrintableEvent(event));
             }
             (global_event::listener::Mode::Grab { .. }, PlaybackMode::Play) => {
                 if let global_event::Event {
@@ -475,41 +1391,251 @@ impl State {
         }
     }
 
-    pub fn view(&self) -> Element<Message> {
+    fn library_view(&self) -> Element<'_, Message> {
+        let library_command = |command| Message::Command(Command::Library(command));
+        let macros = column(
+            self.library
+                .macros
+                .iter()
+                .enumerate()
+                .map(|(index, macro_)| {
+                    let name = format!("{}{}", macro_.name(), if macro_.dirty { "*" } else { "" });
+                    let current = index == self.library.current;
+                    match &self.macro_name_input {
+                        Some(name_input) if current => text_input("Macro name", name_input)
+                            .id(MACRO_NAME_INPUT_ID)
+                            .on_input(move |name| library_command(LibraryCommand::EditName(name)))
+                            .on_submit(library_command(LibraryCommand::Rename))
+                            .into(),
+                        _ => button(text(name))
+                            .width(Length::Fill)
+                            .style(if current {
+                                button::primary
+                            } else {
+                                button::text
+                            })
+                            .on_press(library_command(LibraryCommand::Select(index)))
+                            .into(),
+                    }
+                }),
+        )
+        .spacing(2.0);
+
+        column![
+            widget::scrollable(macros).height(Length::Fill),
+            text_input(
+                "Hotkey, e.g. Ctrl+Alt+F5",
+                &self.library.current().hotkey_input
+            )
+            .on_input(move |hotkey| library_command(LibraryCommand::EditHotkey(hotkey)))
+            .on_submit(library_command(LibraryCommand::BindHotkey)),
+            row![
+                button(text!("New")).on_press(library_command(LibraryCommand::New)),
+                button(text!("Duplicate")).on_press(library_command(LibraryCommand::Duplicate)),
+            ]
+            .spacing(4.0),
+            row![
+                if self.macro_name_input.is_some() {
+                    button(text!("Cancel"))
+                        .on_press(library_command(LibraryCommand::CancelRenaming))
+                } else {
+                    button(text!("Rename")).on_press(library_command(LibraryCommand::StartRenaming))
+                },
+                button(text!("Delete")).on_press(Message::Trigger(Trigger::DeleteMacroButton)),
+            ]
+            .spacing(4.0),
+        ]
+        .spacing(4.0)
+        .width(Length::Fixed(200.0))
+        .into()
+    }
+
+    fn playback_options_view(&self) -> Element<'_, Message> {
+        let options = self.playback_options;
+        let timing_setting: Element<'_, Message> = match options.timing {
+            Timing::Recorded => row![
+                slider(PlaybackOptions::SPEED_RANGE, options.speed, |speed| {
+                    Message::Trigger(Trigger::SpeedSlider(speed))
+                })
+                .step(0.25),
+                text!("{:.2}x", options.speed).width(Length::Fixed(60.0)),
+            ]
+            .spacing(8.0)
+            .into(),
+            Timing::FixedDelay => row![
+                slider(
+                    0..=1000,
+                    options.fixed_delay.as_millis() as u32,
+                    |milliseconds| Message::Trigger(Trigger::FixedDelaySlider(milliseconds))
+                )
+                .step(5u32),
+                text!("{}ms", options.fixed_delay.as_millis()).width(Length::Fixed(60.0)),
+            ]
+            .spacing(8.0)
+            .into(),
+            Timing::AsFastAsPossible => row![].into(),
+        };
+        let repeat_setting: Element<'_, Message> = match options.repeat {
+            Repeat::Times => row![
+                text_input("1", &number_input_value(options.repeat_count))
+                    .on_input(|input| Message::Trigger(Trigger::RepeatCountInput(input)))
+                    .width(Length::Fixed(80.0)),
+                text!("times"),
+            ]
+            .spacing(8.0)
+            .align_y(iced::Alignment::Center)
+            .into(),
+            Repeat::For => row![
+                text_input(
+                    "0",
+                    &number_input_value(options.repeat_duration.as_secs() as u32)
+                )
+                .on_input(|input| Message::Trigger(Trigger::RepeatDurationInput(input)))
+                .width(Length::Fixed(80.0)),
+                text!("s"),
+            ]
+            .spacing(8.0)
+            .align_y(iced::Alignment::Center)
+            .into(),
+            Repeat::UntilStopped => row![].into(),
+        };
+        column![
+            row![
+                pick_list(Timing::ALL, Some(options.timing), |timing| {
+                    Message::Trigger(Trigger::TimingPickList(timing))
+                }),
+                timing_setting,
+            ]
+            .spacing(8.0)
+            .align_y(iced::Alignment::Center),
+            row![
+                pick_list(Repeat::ALL, Some(options.repeat), |repeat| {
+                    Message::Trigger(Trigger::RepeatPickList(repeat))
+                }),
+                repeat_setting,
+                text!("Delay between iterations"),
+                text_input(
+                    "0",
+                    &number_input_value(options.iteration_delay.as_millis() as u32)
+                )
+                .on_input(|input| Message::Trigger(Trigger::IterationDelayInput(input)))
+                .width(Length::Fixed(80.0)),
+                text!("ms"),
+            ]
+            .spacing(8.0)
+            .align_y(iced::Alignment::Center),
+        ]
+        .spacing(4.0)
+        .into()
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let current = self.library.current();
+        let has_selection = current.selected_items_state.get_first_selected().is_some();
+        let is_idle = matches!(self.playback_mode, PlaybackMode::Idle);
         let items = column(
             #[allow(unstable_name_collisions)]
-            self.items
+            current
+                .items
                 .iter()
                 .enumerate()
-                .map(|(index, event)| list_item(index, event, &self.selected_items_state))
+                .map(|(index, event)| match &self.item_editor {
+                    Some(editor) if editor.index == index => item_editor(
+                        editor,
+                        matches!(
+                            self.current_listener_mode,
+                            global_event::listener::Mode::CaptureKey
+                        ),
+                    ),
+                    _ => list_item(
+                        index,
+                        event,
+                        &current.selected_items_state,
+                        current.breakpoints.contains(&index),
+                        self.item_drag == Some(index)
+                            && !current.selected_items_state.is_selected(index),
+                    ),
+                })
                 .intersperse_with(|| separator().into()),
         );
 
-        column![
+        let editor = column![
             row![
                 column![
                     text(format!("{:?}", self.current_listener_mode)),
                     text(format!("{:?}", self.playback_mode)),
+                    text(match self.iteration {
+                        Some((iteration, Some(total))) => format!("Iteration {iteration}/{total}"),
+                        Some((iteration, None)) => format!("Iteration {iteration}"),
+                        None => String::new(),
+                    }),
                 ],
                 checkbox("Always on top", self.always_on_top)
-                    .on_toggle(|value| Message::Trigger(Trigger::AlwaysOnTopCheckbox(value)))
+                    .on_toggle(|value| Message::Trigger(Trigger::AlwaysOnTopCheckbox(value))),
+                checkbox("Record mouse", self.record_mouse)
+                    .on_toggle(|value| Message::Trigger(Trigger::RecordMouseCheckbox(value))),
             ]
             .spacing(8.0)
             .height(Length::Shrink),
             row![
                 button(text!("Record")).on_press(Message::Trigger(Trigger::RecordButton)),
+                pick_list(RecordingMode::ALL, Some(self.recording_mode), |mode| {
+                    Message::Trigger(Trigger::RecordingModePickList(mode))
+                }),
                 button(text!("Play")).on_press(Message::Trigger(Trigger::PlayButton)),
+                button(text!("Play from cursor")).on_press_maybe(
+                    has_selection.then_some(Message::Trigger(Trigger::PlayFromCursorButton))
+                ),
+                button(text!("Play selection")).on_press_maybe(
+                    has_selection.then_some(Message::Trigger(Trigger::PlaySelectionButton))
+                ),
+                if let PlaybackMode::Paused = self.playback_mode {
+                    button(text!("Continue")).on_press(Message::Trigger(Trigger::ResumeButton))
+                } else {
+                    button(text!("Pause")).on_press_maybe(
+                        matches!(self.playback_mode, PlaybackMode::Play)
+                            .then_some(Message::Trigger(Trigger::PauseButton)),
+                    )
+                },
+                button(text!("Step")).on_press_maybe(
+                    matches!(self.playback_mode, PlaybackMode::Paused)
+                        .then_some(Message::Trigger(Trigger::StepButton))
+                ),
                 button(text!("Stop")).on_press(Message::Trigger(Trigger::StopButton)),
-                button(text!("Add yield")).on_press(Message::Trigger(Trigger::AddYieldButton)),
+                button(text!("Breakpoint")).on_press_maybe(
+                    has_selection.then_some(Message::Trigger(Trigger::ToggleBreakpointButton))
+                ),
+                pick_list(EventTemplate::ALL, None::<EventTemplate>, |template| {
+                    Message::Trigger(Trigger::AddEventPickList(template))
+                })
+                .placeholder("Add event"),
+                button(text!("Collapse to text")).on_press_maybe(
+                    has_selection.then_some(Message::Trigger(Trigger::CollapseIntoTextButton))
+                ),
+                button(text!("Convert to chord")).on_press_maybe(
+                    has_selection.then_some(Message::Trigger(Trigger::ConvertIntoChordButton))
+                ),
+                button(text!("Undo")).on_press_maybe(
+                    (is_idle && current.history.can_undo())
+                        .then_some(Message::Trigger(Trigger::UndoButton))
+                ),
+                button(text!("Redo")).on_press_maybe(
+                    (is_idle && current.history.can_redo())
+                        .then_some(Message::Trigger(Trigger::RedoButton))
+                ),
+                button(text!("Open")).on_press(Message::Trigger(Trigger::OpenButton)),
+                button(text!("Save")).on_press(Message::Trigger(Trigger::SaveButton)),
+                button(text!("Save as")).on_press(Message::Trigger(Trigger::SaveAsButton)),
             ]
             .spacing(4.0),
-            if self.items.is_empty() {
+            self.playback_options_view(),
+            if current.items.is_empty() {
                 Element::new(container(text("Press record !").size(24.0)).center(Length::Fill))
             } else {
                 Element::new(
                     widget::scrollable(items)
                         .spacing(8.0)
-                        .id(self.item_list_scroll_id.clone())
+                        .id(current.item_list_scroll_id.clone())
                         .on_scroll(|viewport| {
                             Message::Command(Command::ItemList(ListCommand::SetScrollableViewport(
                                 viewport,
@@ -518,11 +1644,50 @@ impl State {
                 )
             },
         ]
-        .spacing(4.0)
-        .into()
+        .spacing(4.0);
+
+        row![self.library_view(), editor].spacing(8.0).into()
     }
 }
 
+const MACRO_NAME_INPUT_ID: &str = "macro-name";
+const ITEM_INPUT_ID: &str = "item";
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Zero shows as an empty input so that the whole number can be erased while typing
+fn number_input_value(value: u32) -> String {
+    if value == 0 {
+        String::new()
+    } else {
+        value.to_string()
+    }
+}
+
+fn parse_number_input(input: &str) -> Option<u32> {
+    if input.is_empty() {
+        Some(0)
+    } else {
+        input.parse().ok()
+    }
+}
+
+fn save_macro(macro_: &mut library::Macro, path: PathBuf) -> Result<(), String> {
+    let document = macro_file::Document {
+        header: macro_file::Header {
+            name: macro_file::name_from_path(&path),
+            modified: SystemTime::now(),
+            ..macro_.header.clone()
+        },
+        ..macro_.document()
+    };
+    macro_file::save(&path, &document)
+        .map_err(|e| format!("Could not save to {}: {e}", path.display()))?;
+    macro_.header = document.header;
+    macro_.file_path = path;
+    macro_.dirty = false;
+    Ok(())
+}
+
 pub fn theme(_state: &State) -> iced::Theme {
     Theme::Ferra
 }
@@ -531,31 +1696,79 @@ fn list_item<'a, 'b: 'a>(
     index: usize,
     event: &'b PrintableEvent,
     selected_items_state: &'a ItemSelectionState,
+    breakpoint: bool,
+    drop_target: bool,
 ) -> Element<'a, Message> {
+    let list_command = |command| Message::Command(Command::ItemList(command));
     let selected = selected_items_state.is_selected(index);
     mouse_area(
         container(
-            text!("{event}").style(move |theme: &iced::Theme| text::Style {
-                color: if selected {
-                    Some(theme.extended_palette().secondary.base.text)
-                } else {
-                    None
+            text!("{}{event}", if breakpoint { "● " } else { "" }).style(
+                move |theme: &iced::Theme| text::Style {
+                    color: if selected {
+                        Some(theme.extended_palette().secondary.base.text)
+                    } else {
+                        None
+                    },
                 },
-            }),
+            ),
         )
         .width(Length::Fill)
         .padding([4, 4])
         .style(move |theme: &iced::Theme| {
-            if selected {
-                container::background(theme.extended_palette().secondary.base.color)
+            let palette = theme.extended_palette();
+            let style = if selected {
+                container::background(palette.secondary.base.color)
+            } else {
+                container::Style::default()
+            };
+            if drop_target {
+                style.border(Border {
+                    color: palette.primary.strong.color,
+                    width: 2.0,
+                    ..Default::default()
+                })
             } else {
-                Default::default()
+                style
             }
         }),
     )
-    .on_press(Message::Command(Command::ItemList(
-        ListCommand::SelectItem(index),
-    )))
+    .on_press(list_command(ListCommand::SelectItem(index)))
+    .on_enter(list_command(ListCommand::DragOver(index)))
+    .on_release(list_command(ListCommand::Drop(index)))
+    .into()
+}
+
+fn item_editor(editor: &ItemEditor, capturing_key: bool) -> Element<'_, Message> {
+    let list_command = |command| Message::Command(Command::ItemList(command));
+    let commit = editor
+        .error
+        .is_none()
+        .then_some(list_command(ListCommand::CommitItemEdit));
+    column![
+        row![
+            text_input("Event, e.g. press KeyA or wait 120ms", &editor.input)
+                .id(ITEM_INPUT_ID)
+                .on_input(move |input| list_command(ListCommand::EditItemInput(input)))
+                .on_submit_maybe(commit.clone()),
+            button(text!("OK")).on_press_maybe(commit),
+            if capturing_key {
+                button(text!("Press a key…"))
+            } else {
+                button(text!("Capture key")).on_press(list_command(ListCommand::CaptureKey))
+            },
+            button(text!("Cancel")).on_press(list_command(ListCommand::CancelItemEdit)),
+        ]
+        .spacing(4.0),
+    ]
+    .push_maybe(
+        editor
+            .error
+            .as_ref()
+            .map(|error| text(error).style(text::danger)),
+    )
+    .spacing(4.0)
+    .padding([4, 4])
     .into()
 }
 
@@ -565,17 +1778,69 @@ pub fn subscription(_state: &State) -> Subscription<Message> {
 
     let local_keyevent_listener = iced::keyboard::on_key_press(on_key_press);
     let local_event_listener = iced::event::listen_with(on_event);
+    let close_request_listener =
+        iced::window::close_requests().map(|_id| Message::Trigger(Trigger::CloseRequested));
 
     Subscription::batch([
         global_event_listener,
         local_keyevent_listener,
         local_event_listener,
         global_event_player,
+        close_request_listener,
     ])
 }
 
-fn on_key_press(key: Key, _modifiers: Modifiers) -> Option<Message> {
+fn on_key_press(key: Key, modifiers: Modifiers) -> Option<Message> {
+    if modifiers.command() {
+        return match key.as_ref() {
+            Key::Character(c) if c.eq_ignore_ascii_case("s") && modifiers.shift() => {
+                Some(Message::Command(Command::SaveAs))
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("s") => {
+                Some(Message::Command(Command::Save))
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("o") => {
+                Some(Message::Command(Command::Open))
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("z") && modifiers.shift() => {
+                Some(Message::Command(Command::ItemList(ListCommand::Redo)))
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("z") => {
+                Some(Message::Command(Command::ItemList(ListCommand::Undo)))
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("y") => {
+                Some(Message::Command(Command::ItemList(ListCommand::Redo)))
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("c") => {
+                Some(Message::Command(Command::ItemList(ListCommand::Copy)))
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("x") => {
+                Some(Message::Command(Command::ItemList(ListCommand::Cut)))
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("v") => {
+                Some(Message::Command(Command::ItemList(ListCommand::Paste)))
+            }
+            _ => None,
+        };
+    }
+
+    if modifiers.alt() {
+        return match key {
+            Key::Named(Named::ArrowUp) => Some(Message::Command(Command::ItemList(
+                ListCommand::MoveSelectionUp,
+            ))),
+            Key::Named(Named::ArrowDown) => Some(Message::Command(Command::ItemList(
+                ListCommand::MoveSelectionDown,
+            ))),
+            _ => None,
+        };
+    }
+
     match key {
+        Key::Named(Named::Escape) => Some(Message::Trigger(Trigger::EscapeKey)),
+        Key::Named(Named::F9) => Some(Message::Command(Command::ItemList(
+            ListCommand::ToggleBreakpoint,
+        ))),
         Key::Named(Named::Delete) => {
             Some(Message::Command(Command::ItemList(ListCommand::DeleteItem)))
         }
@@ -590,8 +1855,15 @@ fn on_key_press(key: Key, _modifiers: Modifiers) -> Option<Message> {
 }
 
 fn on_event(event: iced::Event, _status: Status, _window: iced::window::Id) -> Option<Message> {
-    if let iced::Event::Keyboard(iced::keyboard::Event::ModifiersChanged(modifiers)) = event {
-        return Some(Message::Command(Command::UpdateModifiers(modifiers)));
+    match event {
+        iced::Event::Keyboard(iced::keyboard::Event::ModifiersChanged(modifiers)) => {
+            Some(Message::Command(Command::UpdateModifiers(modifiers)))
+        }
+        // Whichever widget captured it, a release ends the drag. Widget messages are
+        // handled before subscription ones, so an item dropping the selection goes first.
+        iced::Event::Mouse(iced::mouse::Event::ButtonReleased(iced::mouse::Button::Left)) => {
+            Some(Message::Command(Command::ItemList(ListCommand::EndDrag)))
+        }
+        _ => None,
     }
-    None
 }